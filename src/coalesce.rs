@@ -0,0 +1,211 @@
+use std::collections::{hash_map::Entry, HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, Mutex, MutexGuard,
+};
+
+use crate::mpsc::{RecvError, SendError};
+use crate::utils::claims::Claims;
+
+/// Keys in the order they were first sent, and the latest value sent for each.
+struct Pending<K, V> {
+    order: VecDeque<K>,
+    values: HashMap<K, V>,
+}
+
+pub struct Channel<K, V> {
+    pending: Mutex<Pending<K, V>>,
+    /// Number of keys the receiver may claim, plus one once the last sender is gone.
+    messages: Claims,
+    senders: AtomicUsize,
+    closed: AtomicBool,
+}
+
+impl<K, V> Channel<K, V> {
+    pub fn new() -> Self {
+        Self {
+            pending: Mutex::new(Pending {
+                order: VecDeque::new(),
+                values: HashMap::new(),
+            }),
+            messages: Claims::new(),
+            senders: AtomicUsize::new(1),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    fn pending(&self) -> MutexGuard<'_, Pending<K, V>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<K: Hash + Eq, V> Channel<K, V> {
+    fn pop(&self) -> Option<(K, V)> {
+        let mut pending = self.pending();
+        let key = pending.order.pop_front()?;
+        let value = pending
+            .values
+            .remove(&key)
+            .expect("every queued key has a value");
+        Some((key, value))
+    }
+}
+
+impl<K, V> Default for Channel<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Sender<K, V> {
+    channel: Arc<Channel<K, V>>,
+}
+
+impl<K: Hash + Eq + Clone, V> Sender<K, V> {
+    /// Sends `value` for `key`. If a value for `key` is still waiting to be received, it is
+    /// replaced in place, keeping its position, and returned. Fails if the receiver has been dropped.
+    pub fn send(&self, key: K, value: V) -> Result<Option<V>, SendError<(K, V)>> {
+        let mut pending = self.channel.pending();
+        // Checked under the lock, so the receiver either sees this entry when it clears
+        // the map on drop, or has closed the channel before we got here.
+        if self.channel.closed.load(Ordering::Acquire) {
+            return Err(SendError((key, value)));
+        }
+        match pending.values.entry(key) {
+            Entry::Occupied(mut entry) => return Ok(Some(entry.insert(value))),
+            Entry::Vacant(entry) => {
+                let key = entry.key().clone();
+                entry.insert(value);
+                pending.order.push_back(key);
+            }
+        }
+        drop(pending);
+        self.channel.messages.notify();
+        Ok(None)
+    }
+}
+
+impl<K, V> Clone for Sender<K, V> {
+    fn clone(&self) -> Self {
+        self.channel.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<K, V> Drop for Sender<K, V> {
+    fn drop(&mut self) {
+        if self.channel.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.channel.messages.notify();
+        }
+    }
+}
+
+pub struct Receiver<K, V> {
+    channel: Arc<Channel<K, V>>,
+}
+
+impl<K: Hash + Eq, V> Receiver<K, V> {
+    /// Returns `true` if a key is waiting. A pending disconnect does not count.
+    #[inline]
+    pub fn ready(&self) -> bool {
+        !self.channel.pending().order.is_empty()
+    }
+
+    /// Receives the oldest pending key together with the latest value sent for it.
+    pub fn recv(&self) -> Result<(K, V), RecvError> {
+        if self.channel.messages.count() < 1 && self.channel.senders.load(Ordering::Acquire) < 1 {
+            return Err(RecvError::Disconnected);
+        }
+        self.channel.messages.claim();
+
+        self.channel.pop().ok_or(RecvError::Disconnected)
+    }
+
+    pub fn try_recv(&self) -> Option<(K, V)> {
+        if !self.channel.messages.try_claim() {
+            return None;
+        }
+        let entry = self.channel.pop();
+        if entry.is_none() {
+            self.channel.messages.unclaim();
+        }
+        entry
+    }
+}
+
+impl<K, V> Drop for Receiver<K, V> {
+    fn drop(&mut self) {
+        self.channel.closed.store(true, Ordering::Release);
+        let mut pending = self.channel.pending();
+        pending.order.clear();
+        pending.values.clear();
+    }
+}
+
+/// Creates a channel that keeps only the latest value per key: sending a key that has not been
+/// received yet replaces its value instead of queueing another message.
+pub fn channel<K, V>() -> (Sender<K, V>, Receiver<K, V>) {
+    let channel = Arc::new(Channel::new());
+
+    (
+        Sender {
+            channel: channel.clone(),
+        },
+        Receiver { channel },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_in_place() {
+        let (tx, rx) = channel();
+        assert_eq!(tx.send("a", 1), Ok(None));
+        assert_eq!(tx.send("b", 1), Ok(None));
+        assert_eq!(tx.send("a", 2), Ok(Some(1)));
+        assert_eq!(tx.send("a", 3), Ok(Some(2)));
+        assert_eq!(rx.recv(), Ok(("a", 3)));
+        assert_eq!(tx.send("a", 4), Ok(None));
+        assert_eq!(rx.recv(), Ok(("b", 1)));
+        assert_eq!(rx.recv(), Ok(("a", 4)));
+        assert_eq!(rx.try_recv(), None);
+        drop(tx);
+        assert!(!rx.ready());
+        assert_eq!(rx.recv(), Err(RecvError::Disconnected));
+    }
+
+    #[test]
+    fn send_fails_after_receiver_drop() {
+        let (tx, rx) = channel();
+        tx.send(1, "pending").unwrap();
+        drop(rx);
+        assert_eq!(tx.send(1, "late"), Err(SendError((1, "late"))));
+    }
+
+    #[test]
+    fn send_racing_receiver_drop_leaves_nothing_behind() {
+        let value = Arc::new(());
+        for _ in 0..2000 {
+            let (tx, rx) = channel();
+            let barrier = std::sync::Barrier::new(2);
+            std::thread::scope(|s| {
+                s.spawn(|| {
+                    barrier.wait();
+                    for key in 0.. {
+                        if tx.send(key, value.clone()).is_err() {
+                            break;
+                        }
+                    }
+                });
+                barrier.wait();
+                drop(rx);
+            });
+            assert_eq!(Arc::strong_count(&value), 1);
+        }
+    }
+}
@@ -1,3 +1,8 @@
+pub mod coalesce;
 pub mod mpsc;
 pub mod oneshot;
+pub mod partitioned;
+pub mod pool;
+pub mod ring;
+pub mod spill;
 pub mod utils;
@@ -1,24 +1,395 @@
+use std::cell::UnsafeCell;
 use std::sync::{
-    atomic::{AtomicUsize, Ordering},
-    Arc,
+    atomic::{self, AtomicBool, AtomicUsize, Ordering},
+    Arc, RwLock,
 };
+use std::time::{Duration, Instant};
 
 use crate::utils::{
+    claims::Claims,
     queue::Queue,
-    wait::{wait, wake_one},
+    signal::Signal,
+    wait::{wait, wait_timeout, wake_all, wake_one},
 };
-use crossbeam_epoch::pin;
+use crossbeam_epoch::{pin, Guard};
+
+/// Size of a message, used by [`weighted`] channels to bound their memory use.
+pub trait Weigh {
+    fn weigh(&self) -> usize;
+}
+
+impl Weigh for Vec<u8> {
+    #[inline]
+    fn weigh(&self) -> usize {
+        self.len()
+    }
+}
+
+impl Weigh for String {
+    #[inline]
+    fn weigh(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Budget of a bounded channel. `used` is the total weight of the messages in flight,
+/// with its top bit set once the receiver is gone.
+struct Bound<T> {
+    budget: usize,
+    used: AtomicUsize,
+    /// Futex word senders wait on when the next message does not fit. Bumped whenever room
+    /// is freed or the channel closes, so it changes within the 32 bits the kernel compares,
+    /// which `used` may not.
+    epoch: AtomicUsize,
+    weigher: Box<dyn Fn(&T) -> usize + Send + Sync>,
+}
+
+/// Bit of `Bound::used` set when the receiver is dropped.
+const BOUND_CLOSED: usize = 1 << (usize::BITS - 1);
+
+impl<T> Bound<T> {
+    /// Blocks until `weight` fits into the budget and charges it.
+    /// A message heavier than the whole budget is let through once the channel is empty.
+    /// Returns `false` if the channel got closed in the meantime.
+    fn acquire(&self, weight: usize) -> bool {
+        let mut epoch = self.epoch.load(Ordering::Acquire);
+        let mut used = self.used.load(Ordering::Acquire);
+        loop {
+            if used & BOUND_CLOSED != 0 {
+                return false;
+            }
+            if used != 0 && used.saturating_add(weight) > self.budget {
+                // Any release or close after `epoch` was read changes it, so none is missed.
+                wait(&self.epoch, epoch);
+                epoch = self.epoch.load(Ordering::Acquire);
+                used = self.used.load(Ordering::Acquire);
+                continue;
+            }
+            match self.used.compare_exchange_weak(
+                used,
+                used + weight,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(v) => used = v,
+            }
+        }
+    }
+
+    /// Charges `weight` if it fits right now.
+    fn try_acquire(&self, weight: usize) -> bool {
+        let mut used = self.used.load(Ordering::Acquire);
+        loop {
+            if used & BOUND_CLOSED != 0 || used != 0 && used.saturating_add(weight) > self.budget {
+                return false;
+            }
+            match self.used.compare_exchange_weak(
+                used,
+                used + weight,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(v) => used = v,
+            }
+        }
+    }
+
+    fn release(&self, weight: usize) {
+        self.used.fetch_sub(weight, Ordering::Release);
+        self.wake();
+    }
+
+    /// Fails every sender waiting for room, and every later one.
+    fn close(&self) {
+        self.used.fetch_or(BOUND_CLOSED, Ordering::Release);
+        self.wake();
+    }
+
+    /// Wakes every sender waiting for room, after `used` changed.
+    fn wake(&self) {
+        self.epoch.fetch_add(1, Ordering::Release);
+        wake_all(&self.epoch);
+    }
+}
+
+const PENDING: usize = 0;
+const DELIVERED: usize = 1;
+const RETURNED: usize = 2;
+
+/// Tells a [`Sender::send_confirmed`] caller what became of its message.
+/// `state` is the futex word the sender waits on.
+struct Receipt<T> {
+    state: AtomicUsize,
+    returned: UnsafeCell<Option<T>>,
+}
+
+// Safety: `returned` is written once by whoever drops the message undelivered, before `state` is
+// set to `RETURNED`, and only read by the sender after it observed that.
+unsafe impl<T: Send> Sync for Receipt<T> {}
+
+impl<T> Receipt<T> {
+    fn new() -> Self {
+        Self {
+            state: AtomicUsize::new(PENDING),
+            returned: UnsafeCell::new(None),
+        }
+    }
+
+    /// Blocks until the message has been received, or gives it back if it never will be.
+    fn wait(&self) -> Result<(), SendError<T>> {
+        loop {
+            match self.state.load(Ordering::Acquire) {
+                PENDING => wait(&self.state, PENDING),
+                DELIVERED => return Ok(()),
+                _ => {
+                    let data = unsafe { (*self.returned.get()).take() };
+                    return Err(SendError(data.expect("returned message is taken once")));
+                }
+            }
+        }
+    }
+}
+
+/// A queued message, with the receipt of a confirmed send.
+struct Envelope<T> {
+    data: T,
+    receipt: Option<Arc<Receipt<T>>>,
+}
+
+impl<T> Envelope<T> {
+    /// Hands the message to the receiver, signalling a waiting sender.
+    #[inline]
+    fn open(self) -> T {
+        if let Some(receipt) = self.receipt {
+            receipt.state.store(DELIVERED, Ordering::Release);
+            wake_one(&receipt.state);
+        }
+        self.data
+    }
+
+    /// Drops the message undelivered, giving it back to a waiting sender.
+    fn discard(self) {
+        if let Some(receipt) = self.receipt {
+            unsafe { *receipt.returned.get() = Some(self.data) };
+            receipt.state.store(RETURNED, Ordering::Release);
+            wake_one(&receipt.state);
+        }
+    }
+}
+
+/// A sender's own queue in a [`fair`] channel.
+struct Lane<T> {
+    queue: Queue<Envelope<T>>,
+    /// Set once the owning sender is dropped. The receiver forgets the lane after draining it.
+    orphaned: AtomicBool,
+}
+
+/// The lanes of a [`fair`] channel, visited round-robin by the receiver.
+struct Lanes<T> {
+    lanes: RwLock<Vec<Arc<Lane<T>>>>,
+    next: AtomicUsize,
+}
+
+impl<T> Lanes<T> {
+    fn add(&self) -> Arc<Lane<T>> {
+        let lane = Arc::new(Lane {
+            queue: Queue::new(),
+            orphaned: AtomicBool::new(false),
+        });
+        self.lanes
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(lane.clone());
+        lane
+    }
+
+    /// Pops from the first non-empty lane after the one served last.
+    fn pop(&self, guard: &Guard) -> Option<Envelope<T>> {
+        let lanes = self.lanes.read().unwrap_or_else(|e| e.into_inner());
+        let start = self.next.load(Ordering::Relaxed);
+        let mut prune = false;
+        let mut popped = None;
+        for i in 0..lanes.len() {
+            let index = (start + i) % lanes.len();
+            let lane = &lanes[index];
+            let orphaned = lane.orphaned.load(Ordering::Acquire);
+            if let Some(envelope) = lane.queue.try_pop(guard) {
+                self.next.store(index + 1, Ordering::Relaxed);
+                popped = Some(envelope);
+                break;
+            }
+            prune |= orphaned;
+        }
+        drop(lanes);
+        if prune {
+            // An orphaned lane gets no new messages, so once seen empty it stays empty.
+            self.lanes
+                .write()
+                .unwrap_or_else(|e| e.into_inner())
+                .retain(|lane| {
+                    !lane.orphaned.load(Ordering::Acquire) || !lane.queue.is_empty(guard)
+                });
+        }
+        popped
+    }
+}
 
 pub struct Channel<T> {
-    queue: Queue<T>,
-    messages: AtomicUsize,
+    queue: Queue<Envelope<T>>,
+    /// Number of messages the receiver may claim. The last sender to go away, and every
+    /// interrupt, adds one extra claim with no message behind it, so that a blocked
+    /// receiver wakes up and observes the event.
+    messages: Claims,
+    senders: AtomicUsize,
+    /// Interrupts not yet reported by `recv`.
+    interrupts: AtomicUsize,
+    /// Set when a sender is dropped while its thread is panicking.
+    poisoned: AtomicBool,
+    /// Set when the receiver is dropped, after which nothing is delivered anymore.
+    closed: AtomicBool,
+    bound: Option<Bound<T>>,
+    /// Per-sender queues, used instead of `queue` by [`fair`] channels.
+    lanes: Option<Lanes<T>>,
+    /// Futex word of the [`merge`]d receiver this channel feeds, woken instead of `messages`.
+    signal: Signal,
+    /// Interrupted when the receiver is dropped, so a [`Receiver::tee`] notices its outputs going away.
+    on_close: Option<Interrupter<T>>,
 }
 
 impl<T> Channel<T> {
     pub fn new() -> Self {
         Self {
             queue: Queue::new(),
-            messages: AtomicUsize::new(0),
+            messages: Claims::new(),
+            senders: AtomicUsize::new(1),
+            interrupts: AtomicUsize::new(0),
+            poisoned: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            bound: None,
+            lanes: None,
+            signal: Signal::new(),
+            on_close: None,
+        }
+    }
+
+    /// Creates a channel holding at most `budget` worth of messages, as measured by `weigher`.
+    pub fn with_budget<F>(budget: usize, weigher: F) -> Self
+    where
+        F: Fn(&T) -> usize + Send + Sync + 'static,
+    {
+        Self {
+            bound: Some(Bound {
+                budget,
+                used: AtomicUsize::new(0),
+                epoch: AtomicUsize::new(0),
+                weigher: Box::new(weigher),
+            }),
+            ..Self::new()
+        }
+    }
+
+    /// Creates a channel in which every sender gets its own queue, served round-robin.
+    pub fn fair() -> Self {
+        Self {
+            lanes: Some(Lanes {
+                lanes: RwLock::new(Vec::new()),
+                next: AtomicUsize::new(0),
+            }),
+            ..Self::new()
+        }
+    }
+
+    /// Pops the next envelope and credits its weight back to the waiting senders.
+    #[inline]
+    fn pop_envelope(&self, guard: &Guard) -> Option<Envelope<T>> {
+        let envelope = match &self.lanes {
+            Some(lanes) => lanes.pop(guard)?,
+            None => self.queue.try_pop(guard)?,
+        };
+        if let Some(bound) = &self.bound {
+            bound.release((bound.weigher)(&envelope.data));
+        }
+        Some(envelope)
+    }
+
+    #[inline]
+    fn pop(&self, guard: &Guard) -> Option<T> {
+        self.pop_envelope(guard).map(Envelope::open)
+    }
+
+    /// Returns `true` if a message is queued, ignoring claims that stand for a disconnect or interrupt.
+    fn has_message(&self, guard: &Guard) -> bool {
+        match &self.lanes {
+            Some(lanes) => lanes
+                .lanes
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .iter()
+                .any(|lane| !lane.queue.is_empty(guard)),
+            None => !self.queue.is_empty(guard),
+        }
+    }
+
+    /// Drops every queued message. Used once the receiver is gone.
+    fn drain(&self) {
+        let guard = &pin();
+        while let Some(envelope) = self.pop_envelope(guard) {
+            envelope.discard();
+        }
+    }
+
+    /// Wakes the receiver after `messages` went up.
+    #[inline]
+    fn notify(&self) {
+        if !self.signal.notify() {
+            wake_one(self.messages.futex());
+        }
+    }
+
+    /// Makes `notify` wake a merged receiver through `signal`.
+    fn feed(&self, signal: &Arc<AtomicUsize>) {
+        self.signal.set(Some(signal));
+    }
+
+    /// Consumes a pending interrupt together with the claim it added.
+    fn take_interrupt(&self) -> bool {
+        if self.interrupts.load(Ordering::Acquire) == 0 || !self.messages.try_claim() {
+            return false;
+        }
+        if self.no_message() == RecvError::Interrupted {
+            return true;
+        }
+        self.messages.unclaim();
+        false
+    }
+
+    /// Explains a claim that had no message behind it.
+    #[inline]
+    fn no_message(&self) -> RecvError {
+        let mut interrupts = self.interrupts.load(Ordering::Acquire);
+        while interrupts > 0 {
+            match self.interrupts.compare_exchange(
+                interrupts,
+                interrupts - 1,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return RecvError::Interrupted,
+                Err(v) => interrupts = v,
+            }
+        }
+        self.disconnected()
+    }
+
+    /// Error reported once the channel is drained and every sender is gone.
+    #[inline]
+    fn disconnected(&self) -> RecvError {
+        if self.poisoned.load(Ordering::Acquire) {
+            RecvError::SenderPanicked
+        } else {
+            RecvError::Disconnected
         }
     }
 }
@@ -31,88 +402,563 @@ impl<T> Default for Channel<T> {
 
 pub struct Sender<T> {
     channel: Arc<Channel<T>>,
+    /// This sender's queue in a [`fair`] channel.
+    lane: Option<Arc<Lane<T>>>,
+}
+
+/// Returned by [`Sender::send`] when the receiver is gone, giving the message back.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TrySendError<T> {
+    /// The channel has no room left.
+    Full(T),
+    /// The receiver is gone.
+    Closed(T),
 }
 
 impl<T> Sender<T> {
+    /// Creates a sender over `channel`, with its own lane if the channel is fair.
+    /// The caller accounts for it in `Channel::senders`.
+    fn new(channel: Arc<Channel<T>>) -> Self {
+        let lane = channel.lanes.as_ref().map(Lanes::add);
+        Self { channel, lane }
+    }
+
+    /// Sends `data`, blocking while a bounded channel is full.
+    /// Fails if the receiver has been dropped.
     #[inline]
-    pub fn send(&self, data: T) {
+    pub fn send(&self, data: T) -> Result<(), SendError<T>> {
+        if !self.charge(&data) {
+            return Err(SendError(data));
+        }
+        self.push(data, None);
+        Ok(())
+    }
+
+    /// Sends `data` if a bounded channel has room for it right now.
+    pub fn try_send(&self, data: T) -> Result<(), TrySendError<T>> {
+        if self.is_closed() {
+            return Err(TrySendError::Closed(data));
+        }
+        if let Some(bound) = &self.channel.bound {
+            if !bound.try_acquire((bound.weigher)(&data)) {
+                return Err(TrySendError::Full(data));
+            }
+        }
+        self.push(data, None);
+        Ok(())
+    }
+
+    /// Sends `data` and blocks until the receiver has taken it off the channel.
+    /// Fails, giving `data` back, if the receiver is dropped before that.
+    pub fn send_confirmed(&self, data: T) -> Result<(), SendError<T>> {
+        if !self.charge(&data) {
+            return Err(SendError(data));
+        }
+        let receipt = Arc::new(Receipt::new());
+        self.push(data, Some(receipt.clone()));
+        receipt.wait()
+    }
+
+    /// Waits until `data` fits into a bounded channel and charges its weight.
+    /// Returns `false` if the receiver is gone.
+    #[inline]
+    fn charge(&self, data: &T) -> bool {
+        if self.is_closed() {
+            return false;
+        }
+        match &self.channel.bound {
+            Some(bound) => bound.acquire((bound.weigher)(data)),
+            None => true,
+        }
+    }
+
+    /// Pushes `data` once its capacity has been accounted for.
+    #[inline]
+    fn push(&self, data: T, receipt: Option<Arc<Receipt<T>>>) {
         let guard = &pin();
-        self.channel.queue.push(data, guard);
-        self.channel.messages.fetch_add(1, Ordering::Release);
-        wake_one(&self.channel.messages);
+        let envelope = Envelope { data, receipt };
+        match &self.lane {
+            Some(lane) => lane.queue.push(envelope, guard),
+            None => self.channel.queue.push(envelope, guard),
+        }
+        self.channel.messages.add();
+        self.channel.notify();
+        // The receiver may have gone away while we were pushing, and missed this message when draining.
+        if self.channel.closed.load(Ordering::SeqCst) {
+            self.channel.drain();
+        }
+    }
+
+    /// Waits for room for one message and reserves it.
+    /// On a [`weighted`] channel this reserves a single unit of the budget,
+    /// the rest of the message's weight is charged by [`Permit::send`] without blocking.
+    pub fn reserve(&self) -> Result<Permit<'_, T>, SendError<()>> {
+        self.reserve_units(1)?;
+        Ok(Permit { sender: self })
+    }
+
+    /// Reserves room for one message if there is some right now.
+    pub fn try_reserve(&self) -> Result<Permit<'_, T>, TrySendError<()>> {
+        if self.is_closed() {
+            return Err(TrySendError::Closed(()));
+        }
+        if let Some(bound) = &self.channel.bound {
+            if !bound.try_acquire(1) {
+                return Err(TrySendError::Full(()));
+            }
+        }
+        Ok(Permit { sender: self })
+    }
+
+    /// Waits for room for `n` messages and reserves all of them at once.
+    pub fn reserve_many(&self, n: usize) -> Result<Permits<'_, T>, SendError<()>> {
+        self.reserve_units(n)?;
+        Ok(Permits {
+            sender: self,
+            remaining: n,
+        })
+    }
+
+    fn reserve_units(&self, n: usize) -> Result<(), SendError<()>> {
+        if self.is_closed() {
+            return Err(SendError(()));
+        }
+        if let Some(bound) = &self.channel.bound {
+            if !bound.acquire(n) {
+                return Err(SendError(()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Gives `n` reserved units back.
+    #[inline]
+    fn unreserve(&self, n: usize) {
+        if let Some(bound) = &self.channel.bound {
+            if n > 0 {
+                bound.release(n);
+            }
+        }
+    }
+
+    /// Returns `true` if the receiver has been dropped.
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.channel.closed.load(Ordering::Acquire)
+    }
+}
+
+/// Room for one message, reserved with [`Sender::reserve`].
+/// Dropping it unused gives the room back.
+pub struct Permit<'a, T> {
+    sender: &'a Sender<T>,
+}
+
+impl<T> Permit<'_, T> {
+    /// Sends `data` into the reserved room. If the receiver is gone by now, `data` is dropped.
+    pub fn send(self, data: T) {
+        let sender = self.sender;
+        std::mem::forget(self);
+        if let Some(bound) = &sender.channel.bound {
+            // Settle the difference between the reserved unit and the actual weight.
+            match (bound.weigher)(&data) {
+                0 => bound.release(1),
+                1 => {}
+                weight => {
+                    bound.used.fetch_add(weight - 1, Ordering::Relaxed);
+                }
+            }
+        }
+        sender.push(data, None);
+    }
+}
+
+impl<T> Drop for Permit<'_, T> {
+    fn drop(&mut self) {
+        self.sender.unreserve(1);
+    }
+}
+
+/// Room for several messages, reserved with [`Sender::reserve_many`] and handed out one [`Permit`] at a time.
+/// Dropping it gives the room that was not handed out back.
+pub struct Permits<'a, T> {
+    sender: &'a Sender<T>,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for Permits<'a, T> {
+    type Item = Permit<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        Some(Permit {
+            sender: self.sender,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for Permits<'_, T> {}
+
+impl<T> Drop for Permits<'_, T> {
+    fn drop(&mut self) {
+        self.sender.unreserve(self.remaining);
     }
 }
 
 impl<T> Clone for Sender<T> {
     fn clone(&self) -> Self {
-        Self {
-            channel: self.channel.clone(),
+        self.channel.senders.fetch_add(1, Ordering::Relaxed);
+        Self::new(self.channel.clone())
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.channel.poisoned.store(true, Ordering::Release);
+        }
+        if let Some(lane) = &self.lane {
+            lane.orphaned.store(true, Ordering::Release);
+        }
+        if self.channel.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.channel.messages.add();
+            self.channel.notify();
         }
     }
 }
 
 pub struct Receiver<T> {
     channel: Arc<Channel<T>>,
+    /// Inputs of a receiver created by [`merge`]. Its own `channel` then has no senders
+    /// and only carries interrupts.
+    merged: Option<Box<Merged<T>>>,
+}
+
+/// The inputs of a [`merge`]d receiver, each tagged with its position in the merged list.
+/// Every input's channel wakes `signal` instead of its own `messages`, so the merged
+/// receiver can block on all of them at once.
+struct Merged<T> {
+    sources: Vec<(usize, Receiver<T>)>,
+    signal: Arc<AtomicUsize>,
+    next: AtomicUsize,
 }
 
-#[derive(Debug)]
-pub struct RecvError;
+impl<T> Merged<T> {
+    /// Takes a message from the first input that has one, starting after the one served last.
+    fn try_recv(&self) -> Option<(usize, T)> {
+        let start = self.next.load(Ordering::Relaxed);
+        for i in 0..self.sources.len() {
+            let index = (start + i) % self.sources.len();
+            let (tag, source) = &self.sources[index];
+            if let Some(data) = source.try_recv() {
+                self.next.store(index + 1, Ordering::Relaxed);
+                return Some((*tag, data));
+            }
+        }
+        None
+    }
+
+    fn take_interrupt(&self) -> bool {
+        self.sources
+            .iter()
+            .any(|(_, source)| source.channel.take_interrupt())
+    }
+
+    fn disconnected(&self) -> bool {
+        self.sources
+            .iter()
+            .all(|(_, source)| source.senders_remaining() < 1)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RecvError {
+    /// Every sender is gone and the channel is drained.
+    Disconnected,
+    /// Like `Disconnected`, but at least one sender was dropped during a panic.
+    SenderPanicked,
+    /// The wait was cut short by an [`Interrupter`].
+    Interrupted,
+}
 
 impl<T> Receiver<T> {
+    fn new(channel: Arc<Channel<T>>) -> Self {
+        Self {
+            channel,
+            merged: None,
+        }
+    }
+
     /// Returns the senders remaining of this [`Channel<T>`].
-    /// Since there is always 1 `Receiver<T>` holding the clone of `Channel<T>`, we substract 1.
     #[inline]
     fn senders_remaining(&self) -> usize {
-        Arc::strong_count(&self.channel) - 1
+        self.channel.senders.load(Ordering::Acquire)
     }
 
     #[inline]
     fn messages_remaining(&self) -> usize {
-        self.channel.messages.load(Ordering::Acquire)
+        self.channel.messages.count()
     }
 
+    /// Returns `true` if a message is waiting. A pending disconnect or interrupt does not count.
     #[inline]
     pub fn ready(&self) -> bool {
-        self.messages_remaining() > 0
+        self.channel.has_message(&pin())
+            || self
+                .merged
+                .as_ref()
+                .is_some_and(|merged| merged.sources.iter().any(|(_, source)| source.ready()))
     }
 
     #[inline]
     pub fn recv(&self) -> Result<T, RecvError> {
+        self.recv_tagged().map(|(_, data)| data)
+    }
+
+    fn recv_tagged(&self) -> Result<(usize, T), RecvError> {
+        if let Some(merged) = &self.merged {
+            return self.recv_merged(merged);
+        }
         let guard = &pin();
         if self.messages_remaining() < 1 && self.senders_remaining() < 1 {
-            return Err(RecvError);
+            return Err(self.channel.disconnected());
+        }
+        while !self.channel.messages.try_claim() {
+            wait(self.channel.messages.futex(), 0);
         }
+
+        self.channel
+            .pop(guard)
+            .map(|data| (0, data))
+            .ok_or_else(|| self.channel.no_message())
+    }
+
+    fn recv_merged(&self, merged: &Merged<T>) -> Result<(usize, T), RecvError> {
         loop {
-            let mut messages = self.channel.messages.load(Ordering::Acquire);
-            if messages > 0 {
-                break loop {
-                    match self.channel.messages.compare_exchange(
-                        messages,
-                        messages - 1,
-                        Ordering::Release,
-                        Ordering::Relaxed,
-                    ) {
-                        Ok(_) => break,
-                        Err(v) => {
-                            messages = v;
-                        }
-                    }
-                };
-            } else {
-                wait(&self.channel.messages, messages);
+            let (signal, epoch) = self.futex();
+            if let Some(entry) = merged.try_recv() {
+                return Ok(entry);
+            }
+            if self.channel.take_interrupt() || merged.take_interrupt() {
+                return Err(RecvError::Interrupted);
+            }
+            if merged.disconnected() {
+                // Every input is gone, so whatever they sent is visible by now.
+                if let Some(entry) = merged.try_recv() {
+                    return Ok(entry);
+                }
+                let panicked = merged
+                    .sources
+                    .iter()
+                    .any(|(_, source)| source.channel.disconnected() == RecvError::SenderPanicked);
+                return Err(if panicked {
+                    RecvError::SenderPanicked
+                } else {
+                    RecvError::Disconnected
+                });
             }
+            wait(signal, epoch);
         }
-
-        self.channel.queue.try_pop(guard).ok_or_else(|| RecvError)
     }
 
     #[inline]
     pub fn try_recv(&self) -> Option<T> {
-        if self.senders_remaining() < 1 && self.messages_remaining() < 1 {
+        if let Some(merged) = &self.merged {
+            return merged.try_recv().map(|(_, data)| data);
+        }
+        if !self.channel.messages.try_claim() {
             return None;
         }
         let guard = &pin();
-        self.channel.queue.try_pop(guard)
+        let data = self.channel.pop(guard);
+        if data.is_none() {
+            self.channel.messages.unclaim();
+        }
+        data
+    }
+
+    /// The futex word `recv` blocks on, and the value to block on.
+    #[inline]
+    fn futex(&self) -> (&AtomicUsize, usize) {
+        match &self.merged {
+            Some(merged) => (&merged.signal, merged.signal.load(Ordering::SeqCst)),
+            None => (self.channel.messages.futex(), 0),
+        }
+    }
+
+    /// Returns `true` if `recv` would report a disconnect or interrupt rather than block,
+    /// once `try_recv` found no message.
+    fn pending_event(&self) -> bool {
+        match &self.merged {
+            Some(merged) => {
+                self.channel.interrupts.load(Ordering::Acquire) > 0
+                    || merged
+                        .sources
+                        .iter()
+                        .any(|(_, source)| source.channel.interrupts.load(Ordering::Acquire) > 0)
+                    || merged.disconnected()
+            }
+            None => self.messages_remaining() > 0 || self.senders_remaining() < 1,
+        }
+    }
+
+    /// Blocks for the first message, then keeps collecting until `max_len` messages
+    /// are gathered or `max_wait` has passed since the first one arrived.
+    /// Always returns at least one message. A disconnect or interrupt that happens
+    /// after the first message ends the batch and is reported by the next call.
+    pub fn recv_batch(&self, max_len: usize, max_wait: Duration) -> Result<Vec<T>, RecvError> {
+        let mut batch = vec![self.recv()?];
+        // A wait too long to represent has no deadline.
+        let deadline = Instant::now().checked_add(max_wait);
+        while batch.len() < max_len {
+            let (futex, expected) = self.futex();
+            if let Some(data) = self.try_recv() {
+                batch.push(data);
+                continue;
+            }
+            if self.pending_event() {
+                break;
+            }
+            match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    wait_timeout(futex, expected, deadline - now);
+                }
+                None => wait(futex, expected),
+            }
+        }
+        Ok(batch)
+    }
+
+    /// Returns a handle that can interrupt this receiver's `recv`.
+    pub fn interrupter(&self) -> Interrupter<T> {
+        Interrupter {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+/// What [`Receiver::tee`] does when one of its outputs is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TeePolicy {
+    /// Hold every output back until the slowest one has room again.
+    Block,
+    /// Skip the message for the outputs that are full.
+    DropSlow,
+}
+
+impl<T: Clone + Send + 'static> Receiver<T> {
+    /// Splits this receiver into `n` outputs that each see every message.
+    /// Each output buffers up to `capacity` messages; `policy` decides what happens when one is full.
+    /// Messages are forwarded by a background thread, which stops once the input disconnects
+    /// or every output is dropped.
+    pub fn tee(self, n: usize, capacity: usize, policy: TeePolicy) -> Vec<Receiver<T>> {
+        let interrupter = self.interrupter();
+        let (outputs, receivers): (Vec<_>, Vec<_>) = (0..n)
+            .map(|_| {
+                let channel = Arc::new(Channel {
+                    on_close: Some(interrupter.clone()),
+                    ..Channel::with_budget(capacity, |_| 1)
+                });
+                (Sender::new(channel.clone()), Receiver::new(channel))
+            })
+            .unzip();
+        std::thread::spawn(move || {
+            while !outputs.iter().all(Sender::is_closed) {
+                let data = match self.recv() {
+                    Ok(data) => data,
+                    // An output was dropped; the loop condition decides whether any is left.
+                    Err(RecvError::Interrupted) => continue,
+                    Err(_) => break,
+                };
+                // Outputs that are gone or, with `DropSlow`, full just miss the message.
+                for output in &outputs {
+                    match policy {
+                        TeePolicy::Block => drop(output.send(data.clone())),
+                        TeePolicy::DropSlow => drop(output.try_send(data.clone())),
+                    }
+                }
+            }
+        });
+        receivers
+    }
+}
+
+/// A [`merge`]d receiver that tells which input each message came from.
+pub struct TaggedReceiver<T> {
+    rx: Receiver<T>,
+}
+
+impl<T> TaggedReceiver<T> {
+    #[inline]
+    pub fn ready(&self) -> bool {
+        self.rx.ready()
+    }
+
+    /// Receives the next message along with the index of the input it came from.
+    #[inline]
+    pub fn recv(&self) -> Result<(usize, T), RecvError> {
+        self.rx.recv_tagged()
+    }
+
+    #[inline]
+    pub fn try_recv(&self) -> Option<(usize, T)> {
+        self.rx.merged.as_ref().and_then(|merged| merged.try_recv())
+    }
+
+    /// Returns a handle that can interrupt this receiver's `recv`.
+    pub fn interrupter(&self) -> Interrupter<T> {
+        self.rx.interrupter()
+    }
+}
+
+/// Makes a blocked [`Receiver::recv`] return [`RecvError::Interrupted`], even while senders are alive.
+pub struct Interrupter<T> {
+    channel: Arc<Channel<T>>,
+}
+
+impl<T> Interrupter<T> {
+    /// Interrupts the current `recv`, or the next one that would block if none is waiting.
+    /// Messages already queued are still delivered first.
+    pub fn interrupt(&self) {
+        self.channel.interrupts.fetch_add(1, Ordering::Release);
+        self.channel.messages.add();
+        self.channel.notify();
+    }
+}
+
+impl<T> Clone for Interrupter<T> {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.channel.closed.store(true, Ordering::SeqCst);
+        atomic::fence(Ordering::SeqCst);
+        self.channel.drain();
+        if let Some(bound) = &self.channel.bound {
+            bound.close();
+        }
+        if let Some(interrupter) = &self.channel.on_close {
+            interrupter.interrupt();
+        }
     }
 }
 
@@ -123,9 +969,6 @@ pub struct IntoIter<T> {
 impl<T> Iterator for IntoIter<T> {
     type Item = T;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.rx.senders_remaining() < 1 && self.rx.channel.messages.load(Ordering::Acquire) < 1 {
-            return None;
-        }
         self.rx.recv().ok()
     }
 }
@@ -146,9 +989,6 @@ pub struct Iter<'a, T> {
 impl<T> Iterator for Iter<'_, T> {
     type Item = T;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.rx.senders_remaining() < 1 && self.rx.channel.messages.load(Ordering::Acquire) < 1 {
-            return None;
-        }
         self.rx.recv().ok()
     }
 }
@@ -165,12 +1005,75 @@ impl<'a, T> IntoIterator for &'a Receiver<T> {
 pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
     let channel = Arc::new(Channel::<T>::default());
 
-    (
-        Sender {
-            channel: channel.clone(),
-        },
-        Receiver { channel },
-    )
+    (Sender::new(channel.clone()), Receiver::new(channel))
+}
+
+/// Creates an unbounded channel that does not let one busy sender starve the others:
+/// every clone of the [`Sender`] gets its own queue, and `recv` takes from them in turn.
+/// Messages from the same sender are received in the order they were sent.
+pub fn fair<T>() -> (Sender<T>, Receiver<T>) {
+    let channel = Arc::new(Channel::fair());
+
+    (Sender::new(channel.clone()), Receiver::new(channel))
+}
+
+/// Combines `receivers` into one that yields messages from all of them, without a thread per input.
+/// It blocks on every input at once and disconnects once each of them has disconnected.
+pub fn merge<T>(receivers: Vec<Receiver<T>>) -> Receiver<T> {
+    let signal = Arc::new(AtomicUsize::new(0));
+    let channel = Arc::new(Channel::new());
+    channel.senders.store(0, Ordering::Relaxed);
+    channel.feed(&signal);
+
+    let mut sources = Vec::new();
+    for (tag, mut rx) in receivers.into_iter().enumerate() {
+        // A merged input is flattened; what remains of it still carries its interrupts.
+        if let Some(merged) = rx.merged.take() {
+            sources.extend(merged.sources.into_iter().map(|(_, source)| (tag, source)));
+        }
+        sources.push((tag, rx));
+    }
+    for (_, source) in &sources {
+        source.channel.feed(&signal);
+    }
+    atomic::fence(Ordering::SeqCst);
+
+    Receiver {
+        channel,
+        merged: Some(Box::new(Merged {
+            sources,
+            signal,
+            next: AtomicUsize::new(0),
+        })),
+    }
+}
+
+/// Like [`merge`], but every message comes with the index of the receiver it arrived on.
+pub fn merge_tagged<T>(receivers: Vec<Receiver<T>>) -> TaggedReceiver<T> {
+    TaggedReceiver {
+        rx: merge(receivers),
+    }
+}
+
+/// Creates a channel holding at most `capacity` messages. `send` blocks while it is full.
+pub fn bounded<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    weighted_with(capacity, |_| 1)
+}
+
+/// Creates a channel bounded by the total [`Weigh::weigh`] of the queued messages.
+/// `send` blocks while the next message would exceed `budget`.
+pub fn weighted<T: Weigh + 'static>(budget: usize) -> (Sender<T>, Receiver<T>) {
+    weighted_with(budget, T::weigh)
+}
+
+/// Like [`weighted`], but sizes messages with `weigher`.
+pub fn weighted_with<T, F>(budget: usize, weigher: F) -> (Sender<T>, Receiver<T>)
+where
+    F: Fn(&T) -> usize + Send + Sync + 'static,
+{
+    let channel = Arc::new(Channel::with_budget(budget, weigher));
+
+    (Sender::new(channel.clone()), Receiver::new(channel))
 }
 
 #[cfg(test)]
@@ -182,15 +1085,15 @@ mod tests {
     #[test]
     fn it_works() {
         let (tx, rx) = channel();
-        tx.send(1);
+        tx.send(1).unwrap();
         assert!(rx.ready());
         assert_eq!(rx.recv().unwrap(), 1);
         assert!(rx.try_recv().is_none());
-        tx.send(1);
+        tx.send(1).unwrap();
         assert!(rx.try_recv().is_some());
         assert!(rx.try_recv().is_none());
         let tx1 = tx;
-        tx1.send(1);
+        tx1.send(1).unwrap();
     }
 
     #[test]
@@ -205,7 +1108,7 @@ mod tests {
                 }
             });
             for i in 0..100 {
-                tx.send(i);
+                tx.send(i).unwrap();
             }
             drop(tx);
         });
@@ -217,17 +1120,36 @@ mod tests {
     fn recv_ready() {
         let (tx, rx) = channel();
         assert!(!rx.ready());
-        tx.send(1);
+        tx.send(1).unwrap();
         assert!(rx.ready());
-        assert_eq!(rx.channel.messages.load(SeqCst), 1);
-        tx.send(1);
-        assert_eq!(rx.channel.messages.load(SeqCst), 2);
+        assert_eq!(rx.channel.messages.count(), 1);
+        tx.send(1).unwrap();
+        assert_eq!(rx.channel.messages.count(), 2);
         let _ = rx.recv();
         let _ = rx.recv();
         assert!(!rx.ready());
         assert!(rx.try_recv().is_none());
     }
 
+    #[test]
+    fn ready_ignores_disconnect_and_interrupt() {
+        let (tx, rx) = channel::<i32>();
+        rx.interrupter().interrupt();
+        assert!(!rx.ready());
+        tx.send(1).unwrap();
+        drop(tx);
+        assert!(rx.ready());
+        assert_eq!(rx.recv(), Ok(1));
+        assert!(!rx.ready());
+        assert_eq!(rx.recv(), Err(RecvError::Interrupted));
+        assert_eq!(rx.recv(), Err(RecvError::Disconnected));
+
+        let (tx, rx) = fair::<i32>();
+        let _tx1 = tx.clone();
+        drop(tx);
+        assert!(!rx.ready());
+    }
+
     #[test]
     fn senders_count() {
         let (tx, rx) = channel::<i32>();
@@ -239,4 +1161,410 @@ mod tests {
         drop(tx);
         assert_eq!(rx.senders_remaining(), 0);
     }
+
+    #[test]
+    fn weighted_blocks_until_credited() {
+        let (tx, rx) = weighted::<Vec<u8>>(10);
+        let sent = AtomicBool::new(false);
+        tx.send(vec![0; 6]).unwrap();
+        thread::scope(|s| {
+            s.spawn(|| {
+                tx.send(vec![0; 6]).unwrap();
+                sent.store(true, SeqCst);
+            });
+            thread::sleep(Duration::from_millis(50));
+            assert!(!sent.load(SeqCst));
+            assert_eq!(rx.recv().unwrap().len(), 6);
+        });
+        assert!(sent.load(SeqCst));
+        assert_eq!(rx.recv().unwrap().len(), 6);
+    }
+
+    #[test]
+    fn weighted_lets_oversized_through_when_empty() {
+        let (tx, rx) = weighted_with(4, |s: &String| s.len());
+        tx.send("too long for the budget".to_string()).unwrap();
+        assert_eq!(rx.try_recv().unwrap(), "too long for the budget");
+        tx.send("fits".to_string()).unwrap();
+        assert!(rx.ready());
+    }
+
+    #[test]
+    fn sender_panic_is_reported() {
+        let (tx, rx) = channel();
+        let tx1 = tx.clone();
+        let handle = thread::spawn(move || {
+            tx1.send(1).unwrap();
+            panic!("producer crashed");
+        });
+        assert!(handle.join().is_err());
+        assert_eq!(rx.recv(), Ok(1));
+        drop(tx);
+        assert_eq!(rx.recv(), Err(RecvError::SenderPanicked));
+        assert_eq!(rx.recv(), Err(RecvError::SenderPanicked));
+    }
+
+    #[test]
+    fn blocked_recv_wakes_on_disconnect() {
+        let (tx, rx) = channel::<i32>();
+        thread::scope(|s| {
+            s.spawn(|| assert_eq!(rx.recv(), Err(RecvError::Disconnected)));
+            thread::sleep(Duration::from_millis(50));
+            drop(tx);
+        });
+    }
+
+    #[test]
+    fn receiver_drop_releases_messages() {
+        let (tx, rx) = channel();
+        let payload = Arc::new(());
+        tx.send(payload.clone()).unwrap();
+        tx.send(payload.clone()).unwrap();
+        assert_eq!(Arc::strong_count(&payload), 3);
+        drop(rx);
+        assert_eq!(Arc::strong_count(&payload), 1);
+        assert!(tx.is_closed());
+        assert!(tx.send(payload.clone()).is_err());
+        assert_eq!(Arc::strong_count(&payload), 1);
+    }
+
+    #[test]
+    fn receiver_drop_fails_blocked_sender() {
+        let (tx, rx) = weighted::<Vec<u8>>(4);
+        tx.send(vec![0; 4]).unwrap();
+        thread::scope(|s| {
+            let blocked = s.spawn(|| tx.send(vec![1; 4]));
+            thread::sleep(Duration::from_millis(50));
+            drop(rx);
+            assert_eq!(blocked.join().unwrap(), Err(SendError(vec![1; 4])));
+        });
+    }
+
+    #[test]
+    fn receiver_drop_fails_sender_blocked_behind_permit() {
+        let (tx, rx) = bounded::<i32>(1);
+        let permit = tx.reserve().unwrap();
+        thread::scope(|s| {
+            let blocked = s.spawn(|| tx.send(1));
+            thread::sleep(Duration::from_millis(50));
+            drop(rx);
+            assert_eq!(blocked.join().unwrap(), Err(SendError(1)));
+        });
+        drop(permit);
+        assert!(tx.reserve().is_err());
+    }
+
+    #[test]
+    fn close_changes_the_word_senders_wait_on() {
+        let (tx, rx) = bounded::<i32>(1);
+        let _permit = tx.reserve().unwrap();
+        let bound = tx.channel.bound.as_ref().unwrap();
+        let epoch = bound.epoch.load(SeqCst);
+        drop(rx);
+        // A sender that read `epoch` just before the close must not go to sleep.
+        let start = Instant::now();
+        wait_timeout(&bound.epoch, epoch, Duration::from_secs(2));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn permits_hold_capacity() {
+        let (tx, rx) = bounded(2);
+        let first = tx.reserve().unwrap();
+        let second = tx.try_reserve().unwrap();
+        assert!(matches!(tx.try_reserve(), Err(TrySendError::Full(()))));
+        drop(second);
+        let third = tx.try_reserve().unwrap();
+        first.send(1);
+        third.send(2);
+        assert!(matches!(tx.try_reserve(), Err(TrySendError::Full(()))));
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.recv(), Ok(2));
+        assert!(tx.try_reserve().is_ok());
+    }
+
+    #[test]
+    fn reserve_many_returns_unused() {
+        let (tx, rx) = bounded(3);
+        let mut permits = tx.reserve_many(3).unwrap();
+        assert_eq!(permits.len(), 3);
+        permits.next().unwrap().send(1);
+        assert!(matches!(tx.try_reserve(), Err(TrySendError::Full(()))));
+        drop(permits);
+        tx.reserve_many(2).unwrap().for_each(|p| p.send(2));
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.recv(), Ok(2));
+        drop(rx);
+        assert!(tx.reserve().is_err());
+    }
+
+    #[test]
+    fn send_confirmed_waits_for_recv() {
+        let (tx, rx) = channel();
+        let confirmed = AtomicBool::new(false);
+        thread::scope(|s| {
+            s.spawn(|| {
+                tx.send_confirmed(1).unwrap();
+                confirmed.store(true, SeqCst);
+            });
+            thread::sleep(Duration::from_millis(50));
+            assert!(!confirmed.load(SeqCst));
+            assert_eq!(rx.recv(), Ok(1));
+        });
+        assert!(confirmed.load(SeqCst));
+    }
+
+    #[test]
+    fn send_confirmed_fails_when_receiver_drops() {
+        let (tx, rx) = channel();
+        thread::scope(|s| {
+            let confirmed = s.spawn(|| tx.send_confirmed(String::from("audit")));
+            thread::sleep(Duration::from_millis(50));
+            drop(rx);
+            assert_eq!(
+                confirmed.join().unwrap(),
+                Err(SendError(String::from("audit")))
+            );
+        });
+    }
+
+    #[test]
+    fn interrupt_wakes_blocked_recv() {
+        let (tx, rx) = channel::<i32>();
+        let interrupter = rx.interrupter();
+        thread::scope(|s| {
+            s.spawn(|| assert_eq!(rx.recv(), Err(RecvError::Interrupted)));
+            thread::sleep(Duration::from_millis(50));
+            interrupter.interrupt();
+        });
+        tx.send(1).unwrap();
+        assert_eq!(rx.recv(), Ok(1));
+    }
+
+    #[test]
+    fn interrupt_is_reported_after_queued_messages() {
+        let (tx, rx) = channel();
+        tx.send(1).unwrap();
+        rx.interrupter().interrupt();
+        assert_eq!(rx.try_recv(), Some(1));
+        assert_eq!(rx.try_recv(), None);
+        assert_eq!(rx.recv(), Err(RecvError::Interrupted));
+        drop(tx);
+        assert_eq!(rx.recv(), Err(RecvError::Disconnected));
+    }
+
+    #[test]
+    fn fair_round_robins_senders() {
+        let (noisy, rx) = fair();
+        let quiet = noisy.clone();
+        for i in 0..1000 {
+            noisy.send(i).unwrap();
+        }
+        quiet.send(-1).unwrap();
+        quiet.send(-2).unwrap();
+        let first: Vec<_> = (0..4).map(|_| rx.recv().unwrap()).collect();
+        assert!(first.contains(&-1) && first.contains(&-2));
+        let rest: Vec<_> = (0..998).map(|_| rx.recv().unwrap()).collect();
+        assert!(first.iter().chain(&rest).filter(|&&i| i >= 0).is_sorted());
+    }
+
+    #[test]
+    fn fair_drains_dropped_senders() {
+        let (tx, rx) = fair();
+        let senders: Vec<_> = (0..3).map(|_| tx.clone()).collect();
+        for (i, tx) in senders.into_iter().enumerate() {
+            tx.send(i).unwrap();
+        }
+        drop(tx);
+        let mut received: Vec<_> = (&rx).into_iter().collect();
+        received.sort();
+        assert_eq!(received, [0, 1, 2]);
+        assert!(rx
+            .channel
+            .lanes
+            .as_ref()
+            .unwrap()
+            .lanes
+            .read()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn recv_batch_stops_at_max_len() {
+        let (tx, rx) = channel();
+        for i in 0..10 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(
+            rx.recv_batch(4, Duration::from_secs(60)),
+            Ok(vec![0, 1, 2, 3])
+        );
+        assert_eq!(
+            rx.recv_batch(100, Duration::ZERO),
+            Ok(vec![4, 5, 6, 7, 8, 9])
+        );
+    }
+
+    #[test]
+    fn recv_batch_stops_at_max_wait() {
+        let (tx, rx) = channel();
+        thread::scope(|s| {
+            s.spawn(|| {
+                tx.send(1).unwrap();
+                tx.send(2).unwrap();
+                thread::sleep(Duration::from_millis(200));
+                tx.send(3).unwrap();
+            });
+            let start = Instant::now();
+            assert_eq!(
+                rx.recv_batch(500, Duration::from_millis(20)),
+                Ok(vec![1, 2])
+            );
+            assert!(start.elapsed() < Duration::from_millis(150));
+            assert_eq!(rx.recv_batch(500, Duration::from_millis(20)), Ok(vec![3]));
+        });
+    }
+
+    #[test]
+    fn recv_batch_without_deadline() {
+        let (tx, rx) = channel();
+        thread::scope(|s| {
+            s.spawn(|| {
+                tx.send(1).unwrap();
+                thread::sleep(Duration::from_millis(20));
+                tx.send(2).unwrap();
+            });
+            assert_eq!(rx.recv_batch(2, Duration::MAX), Ok(vec![1, 2]));
+        });
+    }
+
+    #[test]
+    fn recv_batch_leaves_disconnect_for_next_call() {
+        let (tx, rx) = channel();
+        tx.send(1).unwrap();
+        tx.send(2).unwrap();
+        drop(tx);
+        assert_eq!(rx.recv_batch(10, Duration::from_secs(60)), Ok(vec![1, 2]));
+        assert_eq!(
+            rx.recv_batch(10, Duration::from_secs(60)),
+            Err(RecvError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn merge_receives_from_every_input() {
+        let (tx1, rx1) = channel();
+        let (tx2, rx2) = channel();
+        let rx = merge(vec![rx1, rx2]);
+        thread::scope(|s| {
+            s.spawn(|| {
+                let mut received: Vec<_> = rx.into_iter().collect();
+                received.sort();
+                assert_eq!(received, (0..200).collect::<Vec<_>>());
+            });
+            thread::sleep(Duration::from_millis(20));
+            for i in 0..100 {
+                tx1.send(i).unwrap();
+                tx2.send(i + 100).unwrap();
+            }
+            drop(tx1);
+            thread::sleep(Duration::from_millis(20));
+            drop(tx2);
+        });
+    }
+
+    #[test]
+    fn merge_tagged_reports_input() {
+        let (tx1, rx1) = channel();
+        let (tx2, rx2) = channel();
+        let (tx3, rx3) = channel();
+        let rx = merge_tagged(vec![rx1, merge(vec![rx2, rx3])]);
+        tx3.send("c").unwrap();
+        assert_eq!(rx.recv(), Ok((1, "c")));
+        tx1.send("a").unwrap();
+        assert_eq!(rx.recv(), Ok((0, "a")));
+        drop((tx1, tx2));
+        tx3.send("d").unwrap();
+        drop(tx3);
+        assert_eq!(rx.recv(), Ok((1, "d")));
+        assert_eq!(rx.recv(), Err(RecvError::Disconnected));
+    }
+
+    #[test]
+    fn merge_interrupt_and_batch() {
+        let (tx1, rx1) = channel();
+        let (tx2, rx2) = channel();
+        let rx = merge(vec![rx1, rx2]);
+        let interrupter = rx.interrupter();
+        thread::scope(|s| {
+            s.spawn(|| assert_eq!(rx.recv(), Err(RecvError::Interrupted)));
+            thread::sleep(Duration::from_millis(50));
+            interrupter.interrupt();
+        });
+        tx1.send(1).unwrap();
+        tx2.send(2).unwrap();
+        let mut batch = rx.recv_batch(10, Duration::from_millis(20)).unwrap();
+        batch.sort();
+        assert_eq!(batch, [1, 2]);
+    }
+
+    #[test]
+    fn tee_blocking_delivers_everything() {
+        let (tx, rx) = channel();
+        let outputs = rx.tee(3, 2, TeePolicy::Block);
+        thread::scope(|s| {
+            for output in &outputs {
+                s.spawn(move || {
+                    assert_eq!(
+                        output.into_iter().collect::<Vec<_>>(),
+                        (0..100).collect::<Vec<_>>()
+                    )
+                });
+            }
+            for i in 0..100 {
+                tx.send(i).unwrap();
+            }
+            drop(tx);
+        });
+    }
+
+    #[test]
+    fn tee_drop_slow_skips_full_outputs() {
+        let (tx, rx) = channel();
+        let outputs = rx.tee(2, 2, TeePolicy::DropSlow);
+        for i in 0..5 {
+            tx.send_confirmed(i).unwrap();
+        }
+        drop(tx);
+        while outputs.iter().any(|output| output.senders_remaining() > 0) {
+            thread::yield_now();
+        }
+        for output in outputs {
+            assert_eq!(output.into_iter().collect::<Vec<_>>(), [0, 1]);
+        }
+    }
+
+    #[test]
+    fn tee_stops_when_outputs_drop_while_idle() {
+        let (tx, rx) = channel::<i32>();
+        let outputs = rx.tee(2, 2, TeePolicy::Block);
+        thread::sleep(Duration::from_millis(20));
+        drop(outputs);
+        let start = Instant::now();
+        while !tx.is_closed() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::yield_now();
+        }
+        assert_eq!(tx.send(1), Err(SendError(1)));
+    }
+
+    #[test]
+    fn try_send_reports_full_and_closed() {
+        let (tx, rx) = bounded(1);
+        assert_eq!(tx.try_send(1), Ok(()));
+        assert_eq!(tx.try_send(2), Err(TrySendError::Full(2)));
+        drop(rx);
+        assert_eq!(tx.try_send(3), Err(TrySendError::Closed(3)));
+    }
 }
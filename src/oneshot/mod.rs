@@ -1,2 +1,287 @@
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicUsize, Ordering::*};
+use std::time::{Duration, Instant};
+
+use crate::utils::wait::{wait, wait_timeout, wake_all, wake_one};
+
 pub mod oneshot_arc;
 pub mod oneshot_ptr;
+pub mod oneshot_reusable;
+pub mod oneshot_scoped;
+pub mod oneshot_shared;
+
+/// Returned by `recv` when the sender was dropped without sending.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RecvError;
+
+/// Returned by `try_recv` when there is no message to take.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// The message has not been sent yet.
+    Empty,
+    /// The sender was dropped without sending, or the message was already received.
+    Disconnected,
+}
+
+/// Returned by `recv_timeout` when no message could be received in time.
+pub enum RecvTimeoutError<R> {
+    /// No message arrived in time. Holds the receiver, so the caller can keep waiting.
+    Timeout(R),
+    /// The sender was dropped without sending.
+    Disconnected,
+}
+
+impl<R> std::fmt::Debug for RecvTimeoutError<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Timeout(_) => f.write_str("Timeout(..)"),
+            Self::Disconnected => f.write_str("Disconnected"),
+        }
+    }
+}
+
+/// Result of looking for the message: `None` while it has not arrived.
+type Taken<T> = Option<Result<T, RecvError>>;
+
+/// Maps the outcome of a receiver's `take` to what `try_recv` reports.
+fn try_recv_result<T>(taken: Taken<T>) -> Result<T, TryRecvError> {
+    match taken {
+        Some(Ok(message)) => Ok(message),
+        Some(Err(RecvError)) => Err(TryRecvError::Disconnected),
+        None => Err(TryRecvError::Empty),
+    }
+}
+
+/// Maps the outcome of a timed wait to what `recv_timeout` reports, handing `receiver` back on a timeout.
+fn recv_timeout_result<T, R>(taken: Taken<T>, receiver: R) -> Result<T, RecvTimeoutError<R>> {
+    match taken {
+        Some(Ok(message)) => Ok(message),
+        Some(Err(RecvError)) => Err(RecvTimeoutError::Disconnected),
+        None => Err(RecvTimeoutError::Timeout(receiver)),
+    }
+}
+
+/// Calls `take` until it finds the message, sleeping in between with `sleep` for at most the time
+/// left, or for as long as it takes if `timeout` is too long to have a deadline.
+/// Returns `None` once `timeout` has passed.
+fn poll_timeout<T>(
+    timeout: Duration,
+    mut take: impl FnMut() -> Taken<T>,
+    mut sleep: impl FnMut(Option<Duration>),
+) -> Taken<T> {
+    let deadline = Instant::now().checked_add(timeout);
+    loop {
+        if let Some(result) = take() {
+            return Some(result);
+        }
+        match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return None;
+                }
+                sleep(Some(deadline - now));
+            }
+            None => sleep(None),
+        }
+    }
+}
+
+const EMPTY: usize = 0;
+const FULL: usize = 1;
+/// The sender was dropped without sending, or the message has already been received.
+const DISCONNECTED: usize = 2;
+/// The receiver was dropped.
+const RECEIVER_GONE: usize = 4;
+
+/// The message and state word of the futex-based flavours.
+/// The receiver waits on `state` for the message, the sender in `closed` for the receiver to go away.
+struct Slot<T> {
+    message: UnsafeCell<MaybeUninit<T>>,
+    state: AtomicUsize,
+}
+
+unsafe impl<T> Sync for Slot<T> where T: Send {}
+
+impl<T> Slot<T> {
+    const fn new() -> Self {
+        Self {
+            message: UnsafeCell::new(MaybeUninit::uninit()),
+            state: AtomicUsize::new(EMPTY),
+        }
+    }
+
+    /// Empties the slot for another pair of handles, dropping a message left unreceived.
+    fn reset(&mut self) {
+        if *self.state.get_mut() & FULL != 0 {
+            unsafe { self.message.get_mut().assume_init_drop() }
+        }
+        *self.state.get_mut() = EMPTY;
+    }
+
+    /// Stores `message` and wakes the receiver, or hands it back if the receiver has been dropped.
+    fn send(&self, message: T) -> Result<(), T> {
+        if self.is_closed() {
+            return Err(message);
+        }
+        unsafe { (*self.message.get()).write(message) };
+        // `SeqCst`, so a receiver that registers a signal and then checks the slot
+        // either finds the message or has its signal seen by the sender.
+        if self
+            .state
+            .compare_exchange(EMPTY, FULL, SeqCst, Relaxed)
+            .is_err()
+        {
+            // The receiver was dropped while the message was being written.
+            return Err(unsafe { (*self.message.get()).assume_init_read() });
+        }
+        wake_one(&self.state);
+        Ok(())
+    }
+
+    /// Marks the slot disconnected for a sender dropped without sending, and wakes the receiver.
+    /// Returns `false` if the sender had already sent.
+    fn disconnect(&self) -> bool {
+        let disconnected = self
+            .state
+            .compare_exchange(EMPTY, DISCONNECTED, SeqCst, Relaxed)
+            .is_ok();
+        if disconnected {
+            wake_one(&self.state);
+        }
+        disconnected
+    }
+
+    /// Marks the receiver gone, waking every thread blocked in `closed`:
+    /// the sender is `Sync`, so there may be several.
+    fn close(&self) {
+        if self.state.fetch_or(RECEIVER_GONE, Release) == EMPTY {
+            wake_all(&self.state);
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state.load(Acquire) & RECEIVER_GONE != 0
+    }
+
+    /// Blocks until the receiver has been dropped.
+    fn closed(&self) {
+        loop {
+            let state = self.state.load(Acquire);
+            if state & RECEIVER_GONE != 0 {
+                return;
+            }
+            wait(&self.state, state);
+        }
+    }
+
+    fn is_ready(&self) -> bool {
+        self.state.load(Relaxed) != EMPTY
+    }
+
+    /// Takes the message if the sender is done with the slot.
+    fn take(&self) -> Taken<T> {
+        match self
+            .state
+            .compare_exchange(FULL, DISCONNECTED, Acquire, Acquire)
+        {
+            Ok(_) => Some(Ok(unsafe { (*self.message.get()).assume_init_read() })),
+            Err(DISCONNECTED) => Some(Err(RecvError)),
+            Err(_) => None,
+        }
+    }
+
+    fn recv(&self) -> Result<T, RecvError> {
+        loop {
+            if let Some(result) = self.take() {
+                return result;
+            }
+            wait(&self.state, EMPTY);
+        }
+    }
+
+    fn recv_timeout(&self, timeout: Duration) -> Taken<T> {
+        poll_timeout(
+            timeout,
+            || self.take(),
+            |left| match left {
+                Some(left) => wait_timeout(&self.state, EMPTY, left),
+                None => wait(&self.state, EMPTY),
+            },
+        )
+    }
+}
+
+impl<T> Drop for Slot<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() & FULL != 0 {
+            unsafe { self.message.get_mut().assume_init_drop() }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn try_recv_and_timeout() {
+        let slot = Slot::new();
+        assert_eq!(try_recv_result(slot.take()), Err(TryRecvError::Empty));
+        assert!(slot.recv_timeout(Duration::from_millis(10)).is_none());
+        thread::scope(|s| {
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(20));
+                slot.send(1).unwrap();
+            });
+            assert_eq!(slot.recv_timeout(Duration::from_secs(5)), Some(Ok(1)));
+        });
+        assert_eq!(
+            try_recv_result(slot.take()),
+            Err(TryRecvError::Disconnected)
+        );
+
+        let slot = Slot::new();
+        slot.send(2).unwrap();
+        assert_eq!(slot.recv_timeout(Duration::MAX), Some(Ok(2)));
+        let slot = Slot::<i32>::new();
+        thread::scope(|s| {
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(20));
+                slot.disconnect();
+            });
+            assert_eq!(slot.recv_timeout(Duration::MAX), Some(Err(RecvError)));
+        });
+    }
+
+    #[test]
+    fn closed_wakes_sender() {
+        let slot = Slot::<i32>::new();
+        assert!(!slot.is_closed());
+        thread::scope(|s| {
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(20));
+                slot.close();
+            });
+            slot.closed();
+        });
+        assert!(slot.is_closed());
+        assert_eq!(slot.send(1), Err(1));
+    }
+
+    #[test]
+    fn closed_wakes_every_waiter() {
+        let (tx, rx) = oneshot_arc::channel::<i32>();
+        thread::scope(|s| {
+            let waiters: Vec<_> = (0..2).map(|_| s.spawn(|| tx.closed())).collect();
+            thread::sleep(Duration::from_millis(20));
+            drop(rx);
+            for waiter in waiters {
+                waiter.join().unwrap();
+            }
+        });
+        assert!(tx.is_closed());
+    }
+}
@@ -1,75 +1,390 @@
+use std::sync::atomic::{self, AtomicUsize, Ordering::*};
 use std::sync::Arc;
-use std::thread::Thread;
-use std::{cell::UnsafeCell, thread};
-use std::{marker::PhantomData, sync::atomic::Ordering::*};
-use std::{mem::MaybeUninit, sync::atomic::AtomicBool};
+#[cfg(feature = "async")]
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+#[cfg(feature = "async")]
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use super::{
+    recv_timeout_result, try_recv_result, RecvError, RecvTimeoutError, Slot, TryRecvError,
+};
+use crate::utils::{signal::Signal, wait::wait};
 
 pub struct OneShot<T> {
-    message: UnsafeCell<MaybeUninit<T>>,
-    ready: AtomicBool,
+    slot: Slot<T>,
+    /// Task awaiting the receiver, woken alongside the futex.
+    #[cfg(feature = "async")]
+    waker: Mutex<Option<Waker>>,
+    /// Futex word of a [`join_all`] or [`select_first`] waiting on this receiver among others.
+    signal: Signal,
 }
 
-unsafe impl<T> Sync for OneShot<T> where T: Send {}
-
 impl<T> OneShot<T> {
     pub const fn new() -> Self {
         Self {
-            message: UnsafeCell::new(MaybeUninit::uninit()),
-            ready: AtomicBool::new(false),
+            slot: Slot::new(),
+            #[cfg(feature = "async")]
+            waker: Mutex::new(None),
+            signal: Signal::new(),
         }
     }
-}
 
-impl<T> Drop for OneShot<T> {
-    fn drop(&mut self) {
-        if *self.ready.get_mut() {
-            unsafe { self.message.get_mut().assume_init_drop() }
+    /// Wakes a receiver that is awaited or waited on together with others.
+    /// One blocked in `recv` is woken by the slot itself.
+    fn notify(&self) {
+        self.signal.notify();
+        #[cfg(feature = "async")]
+        if let Some(waker) = self.waker().take() {
+            waker.wake();
         }
     }
+
+    #[cfg(feature = "async")]
+    fn waker(&self) -> MutexGuard<'_, Option<Waker>> {
+        self.waker.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<T> Default for OneShot<T> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 pub struct Sender<T> {
     channel: Arc<OneShot<T>>,
-    receiving_thread: Thread,
 }
 
+/// Can be moved to another thread before calling `recv`: whichever thread waits gets woken.
 pub struct Receiver<T> {
     channel: Arc<OneShot<T>>,
-    _no_send: PhantomData<*const ()>,
 }
 
 impl<T> Sender<T> {
-    pub fn send(self, message: T) {
-        unsafe { (*self.channel.message.get()).write(message) };
-        self.channel.ready.store(true, Release);
-        self.receiving_thread.unpark();
+    /// Sends `message`, or hands it back if the receiver has been dropped.
+    pub fn send(self, message: T) -> Result<(), T> {
+        self.channel.slot.send(message)?;
+        self.channel.notify();
+        Ok(())
+    }
+
+    /// Returns `true` if the receiver has been dropped, so sending would fail.
+    pub fn is_closed(&self) -> bool {
+        self.channel.slot.is_closed()
+    }
+
+    /// Blocks until the receiver has been dropped.
+    pub fn closed(&self) {
+        self.channel.slot.closed()
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if self.channel.slot.disconnect() {
+            self.channel.notify();
+        }
     }
 }
 
 impl<T> Receiver<T> {
+    /// Returns `true` if `recv` would not block.
+    pub fn is_ready(&self) -> bool {
+        self.channel.slot.is_ready()
+    }
+
+    /// Blocks until the message arrives, or fails if the sender is dropped without sending.
+    pub fn recv(self) -> Result<T, RecvError> {
+        self.channel.slot.recv()
+    }
+
+    /// Takes the message without blocking.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        try_recv_result(self.channel.slot.take())
+    }
+
+    /// Like `recv`, but gives up once `timeout` has passed and hands the receiver back.
+    pub fn recv_timeout(self, timeout: Duration) -> Result<T, RecvTimeoutError<Self>> {
+        recv_timeout_result(self.channel.slot.recv_timeout(timeout), self)
+    }
+
+    /// Returns a receiver that applies `f` to the message once it arrives.
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Map<T, F> {
+        Map { receiver: self, f }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.channel.slot.close();
+    }
+}
+
+/// Resolves like `recv`, without blocking the executor.
+#[cfg(feature = "async")]
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(result) = self.channel.slot.take() {
+            return Poll::Ready(result);
+        }
+        // Registered before checking again, so a send in between still wakes this task.
+        self.channel.waker().replace(cx.waker().clone());
+        match self.channel.slot.take() {
+            Some(result) => Poll::Ready(result),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Returned by [`Receiver::map`].
+pub struct Map<T, F> {
+    receiver: Receiver<T>,
+    f: F,
+}
+
+impl<T, U, F: FnOnce(T) -> U> Map<T, F> {
+    /// Returns `true` if `recv` would not block.
     pub fn is_ready(&self) -> bool {
-        self.channel.ready.load(Relaxed)
+        self.receiver.is_ready()
+    }
+
+    /// Blocks until the message arrives and maps it, or fails if the sender is dropped without sending.
+    pub fn recv(self) -> Result<U, RecvError> {
+        self.receiver.recv().map(self.f)
     }
 
-    pub fn recv(self) -> T {
-        while !self.channel.ready.swap(false, Acquire) {
-            thread::park();
+    /// Like `recv`, but gives up once `timeout` has passed and hands the receiver back.
+    pub fn recv_timeout(self, timeout: Duration) -> Result<U, RecvTimeoutError<Self>> {
+        let Self { receiver, f } = self;
+        match receiver.recv_timeout(timeout) {
+            Ok(message) => Ok(f(message)),
+            Err(RecvTimeoutError::Timeout(receiver)) => {
+                Err(RecvTimeoutError::Timeout(Self { receiver, f }))
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(RecvTimeoutError::Disconnected),
         }
-        unsafe { (*self.channel.message.get()).assume_init_read() }
     }
 }
 
+/// Calls `poll` until it returns `Some`, sleeping on one futex word
+/// that every sender in `receivers` wakes, instead of on each receiver in turn.
+/// A receiver has room for one such signal, so the caller must hold `receivers` exclusively.
+fn wait_any<T, R>(receivers: &[Receiver<T>], mut poll: impl FnMut() -> Option<R>) -> R {
+    let signal = Arc::new(AtomicUsize::new(0));
+    for receiver in receivers {
+        receiver.channel.signal.set(Some(&signal));
+    }
+    // Pairs with the `SeqCst` update of the slot, see `Slot::send`.
+    atomic::fence(SeqCst);
+    let result = loop {
+        let epoch = signal.load(SeqCst);
+        if let Some(result) = poll() {
+            break result;
+        }
+        wait(&signal, epoch);
+    };
+    for receiver in receivers {
+        receiver.channel.signal.set(None);
+    }
+    result
+}
+
+/// Blocks until every receiver has its message and returns them in order.
+/// Fails as soon as any sender is dropped without sending.
+pub fn join_all<T>(receivers: Vec<Receiver<T>>) -> Result<Vec<T>, RecvError> {
+    let mut messages: Vec<Option<T>> = receivers.iter().map(|_| None).collect();
+    let mut pending = receivers.len();
+    wait_any(&receivers, || {
+        for (receiver, message) in receivers.iter().zip(&mut messages) {
+            if message.is_some() {
+                continue;
+            }
+            match receiver.channel.slot.take() {
+                Some(Ok(m)) => {
+                    *message = Some(m);
+                    pending -= 1;
+                }
+                Some(Err(RecvError)) => return Some(Err(RecvError)),
+                None => {}
+            }
+        }
+        (pending == 0).then_some(Ok(()))
+    })?;
+    Ok(messages.into_iter().flatten().collect())
+}
+
+/// Blocks until one of the receivers has its message and returns it with the receiver's index.
+/// Receivers whose message was already taken, or whose sender was dropped without sending,
+/// are skipped; fails once none are left. Calling it again on the same receivers
+/// returns the next message to arrive.
+pub fn select_first<T>(receivers: &mut [Receiver<T>]) -> Result<(usize, T), RecvError> {
+    let receivers = &*receivers;
+    wait_any(receivers, || {
+        let mut done = 0;
+        for (i, receiver) in receivers.iter().enumerate() {
+            match receiver.channel.slot.take() {
+                Some(Ok(message)) => return Some(Ok((i, message))),
+                Some(Err(RecvError)) => done += 1,
+                None => {}
+            }
+        }
+        (done == receivers.len()).then_some(Err(RecvError))
+    })
+}
+
 pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
     let channel = Arc::new(OneShot::<T>::new());
 
     (
         Sender {
             channel: channel.clone(),
-            receiving_thread: thread::current(),
-        },
-        Receiver {
-            channel,
-            _no_send: PhantomData,
         },
+        Receiver { channel },
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn send_recv() {
+        let (tx, rx) = channel();
+        thread::scope(|s| {
+            s.spawn(move || tx.send(1));
+        });
+        assert!(rx.is_ready());
+        assert_eq!(rx.recv(), Ok(1));
+    }
+
+    #[test]
+    fn dropped_sender_disconnects() {
+        let (tx, rx) = channel::<i32>();
+        thread::scope(|s| {
+            s.spawn(move || drop(tx));
+        });
+        assert_eq!(rx.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn recv_on_another_thread() {
+        let (tx, rx) = channel();
+        thread::scope(|s| {
+            let waiter = s.spawn(move || rx.recv());
+            thread::sleep(std::time::Duration::from_millis(50));
+            tx.send(1).unwrap();
+            assert_eq!(waiter.join().unwrap(), Ok(1));
+        });
+    }
+
+    #[test]
+    fn recv_timeout_without_deadline() {
+        let (tx, rx) = channel();
+        tx.send(1).unwrap();
+        assert_eq!(rx.recv_timeout(Duration::MAX).unwrap(), 1);
+        let (tx, rx) = channel();
+        tx.send(2).unwrap();
+        assert_eq!(
+            rx.map(|n: i32| n * 2).recv_timeout(Duration::MAX).unwrap(),
+            4
+        );
+    }
+
+    #[test]
+    fn join_all_waits_for_every_message() {
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..8).map(|_| channel()).unzip();
+        thread::scope(|s| {
+            for (i, tx) in senders.into_iter().enumerate().rev() {
+                s.spawn(move || {
+                    thread::sleep(Duration::from_millis(2 * (8 - i as u64)));
+                    tx.send(i).unwrap();
+                });
+            }
+            assert_eq!(join_all(receivers), Ok((0..8).collect()));
+        });
+
+        let (mut senders, receivers): (Vec<_>, Vec<_>) = (0..3).map(|_| channel()).unzip();
+        senders.pop().unwrap().send(2).unwrap();
+        drop(senders.remove(0));
+        assert_eq!(join_all(receivers), Err(RecvError));
+    }
+
+    #[test]
+    fn select_first_returns_each_message_once() {
+        let (mut senders, mut receivers): (Vec<_>, Vec<_>) = (0..3).map(|_| channel()).unzip();
+        drop(senders.remove(0));
+        thread::scope(|s| {
+            let tx = senders.pop().unwrap();
+            s.spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                tx.send("last").unwrap();
+            });
+            assert_eq!(select_first(&mut receivers), Ok((2, "last")));
+        });
+        senders.pop().unwrap().send("middle").unwrap();
+        assert_eq!(select_first(&mut receivers), Ok((1, "middle")));
+        assert_eq!(select_first(&mut receivers), Err(RecvError));
+    }
+
+    #[test]
+    fn map_message() {
+        let (tx, rx) = channel();
+        let rx = match rx
+            .map(|n: i32| n.to_string())
+            .recv_timeout(Duration::from_millis(10))
+        {
+            Err(RecvTimeoutError::Timeout(rx)) => rx,
+            other => panic!("expected a timeout, got {other:?}"),
+        };
+        assert!(!rx.is_ready());
+        tx.send(7).unwrap();
+        assert_eq!(rx.recv(), Ok(String::from("7")));
+    }
+
+    #[cfg(feature = "async")]
+    fn block_on<F: Future>(future: F) -> F::Output {
+        use std::task::Wake;
+
+        struct Unpark(thread::Thread);
+
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn await_receiver() {
+        let (tx, rx) = channel();
+        thread::scope(|s| {
+            s.spawn(move || {
+                thread::sleep(std::time::Duration::from_millis(50));
+                tx.send(1).unwrap();
+            });
+            assert_eq!(block_on(rx), Ok(1));
+        });
+        let (tx, rx) = channel::<i32>();
+        drop(tx);
+        assert_eq!(block_on(rx), Err(RecvError));
+    }
+}
@@ -1,11 +1,28 @@
 use std::sync::atomic::Ordering::*;
 use std::thread::Thread;
+use std::time::Duration;
 use std::{cell::UnsafeCell, thread};
-use std::{mem::MaybeUninit, sync::atomic::AtomicBool};
+use std::{mem::MaybeUninit, ptr, sync::atomic::AtomicU8};
+
+use super::{
+    poll_timeout, recv_timeout_result, try_recv_result, RecvError, RecvTimeoutError, Taken,
+    TryRecvError,
+};
+
+const EMPTY: u8 = 0;
+const FULL: u8 = 1;
+/// The sender was dropped without sending, or the message has already been received.
+const DISCONNECTED: u8 = 2;
+/// The receiver is gone. Whichever handle finds the other one done frees the channel.
+const RECEIVER_GONE: u8 = 4;
+/// The sender is blocked in `closed`, and has stored its thread for the receiver to unpark.
+const SENDER_WAITING: u8 = 8;
 
 pub struct OneShot<T> {
     message: UnsafeCell<MaybeUninit<T>>,
-    ready: AtomicBool,
+    state: AtomicU8,
+    /// Written once by the sender before it sets `SENDER_WAITING`, read by the receiver after.
+    sending_thread: UnsafeCell<Option<Thread>>,
 }
 
 unsafe impl<T> Sync for OneShot<T> where T: Send {}
@@ -14,7 +31,8 @@ impl<T> OneShot<T> {
     pub const fn new() -> Self {
         Self {
             message: UnsafeCell::new(MaybeUninit::uninit()),
-            ready: AtomicBool::new(false),
+            state: AtomicU8::new(EMPTY),
+            sending_thread: UnsafeCell::new(None),
         }
     }
 
@@ -30,21 +48,29 @@ impl<T> OneShot<T> {
     }
 }
 
+impl<T> Default for OneShot<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T> Drop for OneShot<T> {
     fn drop(&mut self) {
-        if *self.ready.get_mut() {
+        if *self.state.get_mut() & FULL != 0 {
             unsafe { self.message.get_mut().assume_init_drop() }
         }
     }
 }
 
 pub struct Sender<T> {
+    /// Null once the message has been sent, the channel may already be freed then.
     channel: *mut OneShot<T>,
     receiving_thread: Thread,
 }
 
-// Safety: Sender<T> can not outlive Receiver<T>, which is not Send nor Sync.
-unsafe impl<T> Send for Sender<T> {}
+// Safety: the channel is only freed by the last handle to let go of it,
+// so the message is the only thing moving between threads.
+unsafe impl<T: Send> Send for Sender<T> {}
 
 pub struct Receiver<T> {
     channel: *mut OneShot<T>,
@@ -54,11 +80,58 @@ impl<T> Sender<T> {
     fn channel(&self) -> &OneShot<T> {
         unsafe { &*self.channel }
     }
-    pub fn send(self, message: T) {
+
+    /// Marks the sender done with `state` and wakes the receiver. If the receiver is already gone,
+    /// the channel is handed back to be freed. The channel must not be touched afterwards.
+    fn finish(&mut self, state: u8) -> Option<Box<OneShot<T>>> {
+        let channel = std::mem::replace(&mut self.channel, ptr::null_mut());
+        if unsafe { &*channel }.state.fetch_or(state, AcqRel) & RECEIVER_GONE != 0 {
+            Some(unsafe { Box::from_raw(channel) })
+        } else {
+            self.receiving_thread.unpark();
+            None
+        }
+    }
+
+    /// Sends `message`, or hands it back if the receiver has been dropped.
+    pub fn send(mut self, message: T) -> Result<(), T> {
+        if self.is_closed() {
+            return Err(message);
+        }
+        unsafe { (*self.channel().message.get()).write(message) };
+        match self.finish(FULL) {
+            None => Ok(()),
+            Some(mut channel) => {
+                // The receiver was dropped while the message was being written.
+                *channel.state.get_mut() = RECEIVER_GONE;
+                Err(unsafe { channel.message.get_mut().assume_init_read() })
+            }
+        }
+    }
+
+    /// Returns `true` if the receiver has been dropped, so sending would fail.
+    pub fn is_closed(&self) -> bool {
+        self.channel().state.load(Acquire) & RECEIVER_GONE != 0
+    }
+
+    /// Blocks until the receiver has been dropped.
+    pub fn closed(&self) {
         let channel = self.channel();
-        unsafe { (*channel.message.get()).write(message) };
-        channel.ready.store(true, Release);
-        self.receiving_thread.unpark();
+        if channel.state.load(Relaxed) & SENDER_WAITING == 0 {
+            unsafe { *channel.sending_thread.get() = Some(thread::current()) };
+            channel.state.fetch_or(SENDER_WAITING, Release);
+        }
+        while channel.state.load(Acquire) & RECEIVER_GONE == 0 {
+            thread::park();
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if !self.channel.is_null() {
+            self.finish(DISCONNECTED);
+        }
     }
 }
 
@@ -66,21 +139,76 @@ impl<T> Receiver<T> {
     fn channel(&self) -> &OneShot<T> {
         unsafe { &*self.channel }
     }
+    /// Returns `true` if `recv` would not block.
     pub fn is_ready(&self) -> bool {
-        self.channel().ready.load(Relaxed)
+        self.channel().state.load(Relaxed) & (FULL | DISCONNECTED) != 0
     }
 
-    pub fn recv(self) -> T {
-        while !self.channel().ready.swap(false, Acquire) {
+    /// Takes the message if the sender is done with the channel.
+    fn take(&self) -> Taken<T> {
+        let channel = self.channel();
+        match channel.state.load(Acquire) & (FULL | DISCONNECTED) {
+            FULL => {
+                // The sender is done, so nobody else touches the state until we drop.
+                channel.state.store(DISCONNECTED, Relaxed);
+                Some(Ok(unsafe { (*channel.message.get()).assume_init_read() }))
+            }
+            DISCONNECTED => Some(Err(RecvError)),
+            _ => None,
+        }
+    }
+
+    /// Blocks until the message arrives, or fails if the sender is dropped without sending.
+    pub fn recv(self) -> Result<T, RecvError> {
+        loop {
+            if let Some(result) = self.take() {
+                return result;
+            }
             thread::park();
         }
-        unsafe { (*self.channel().message.get()).assume_init_read() }
+    }
+
+    /// Takes the message without blocking.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        try_recv_result(self.take())
+    }
+
+    /// Like `recv`, but gives up once `timeout` has passed and hands the receiver back.
+    pub fn recv_timeout(self, timeout: Duration) -> Result<T, RecvTimeoutError<Self>> {
+        let taken = poll_timeout(
+            timeout,
+            || self.take(),
+            |left| match left {
+                Some(left) => thread::park_timeout(left),
+                None => thread::park(),
+            },
+        );
+        recv_timeout_result(taken, self)
     }
 }
 
 impl<T> std::ops::Drop for Receiver<T> {
     fn drop(&mut self) {
-        let _ = unsafe { Box::from_raw(self.channel) };
+        let channel = self.channel();
+        let mut state = channel.state.load(Acquire);
+        let waiting = loop {
+            // Once it sees `RECEIVER_GONE` the sender may free the channel, so its thread is cloned first.
+            let waiting = (state & SENDER_WAITING != 0)
+                .then(|| unsafe { (*channel.sending_thread.get()).clone() })
+                .flatten();
+            match channel
+                .state
+                .compare_exchange_weak(state, state | RECEIVER_GONE, AcqRel, Acquire)
+            {
+                Ok(_) => break waiting,
+                Err(s) => state = s,
+            }
+        };
+        if state & (FULL | DISCONNECTED) != 0 {
+            drop(unsafe { Box::from_raw(self.channel) });
+        } else if let Some(thread) = waiting {
+            thread.unpark();
+        }
     }
 }
 
@@ -95,3 +223,123 @@ pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
         Receiver { channel },
     )
 }
+
+/// The channel is freed by whichever handle lets go last, so these tests are meant to be run
+/// under Miri as well, across several schedules:
+///
+/// ```text
+/// MIRIFLAGS="-Zmiri-many-seeds=0..16" cargo +nightly miri test oneshot_ptr
+/// ```
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    /// Rounds of the racing tests, fewer under Miri where every round is much slower.
+    const ROUNDS: usize = if cfg!(miri) { 20 } else { 1000 };
+
+    /// Counts its drops, so tests can check the message is dropped exactly once.
+    struct Tracked<'a>(&'a AtomicUsize);
+
+    impl Drop for Tracked<'_> {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Relaxed);
+        }
+    }
+
+    #[test]
+    fn send_recv() {
+        let (tx, rx) = channel();
+        thread::scope(|s| {
+            s.spawn(move || tx.send(1));
+        });
+        assert!(rx.is_ready());
+        assert_eq!(rx.recv(), Ok(1));
+    }
+
+    #[test]
+    fn dropped_sender_disconnects() {
+        let (tx, rx) = channel::<i32>();
+        thread::scope(|s| {
+            s.spawn(move || drop(tx));
+        });
+        assert_eq!(rx.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn try_recv_and_timeout() {
+        let (tx, mut rx) = channel();
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        let mut rx = match rx.recv_timeout(Duration::from_millis(10)) {
+            Err(RecvTimeoutError::Timeout(rx)) => rx,
+            other => panic!("expected a timeout, got {other:?}"),
+        };
+        thread::scope(|s| {
+            s.spawn(move || tx.send(1));
+        });
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+
+        let (tx, rx) = channel();
+        tx.send(2).unwrap();
+        assert_eq!(rx.recv_timeout(Duration::MAX).unwrap(), 2);
+    }
+
+    #[test]
+    fn send_after_receiver_dropped() {
+        let drops = &AtomicUsize::new(0);
+        let (tx, rx) = channel();
+        drop(rx);
+        thread::scope(|s| {
+            s.spawn(move || {
+                assert!(tx.is_closed());
+                assert!(tx.send(Tracked(drops)).is_err());
+            });
+        });
+        assert_eq!(drops.load(Relaxed), 1);
+    }
+
+    #[test]
+    fn unreceived_message_dropped_with_receiver() {
+        let drops = &AtomicUsize::new(0);
+        let (tx, rx) = channel();
+        assert!(tx.send(Tracked(drops)).is_ok());
+        assert_eq!(drops.load(Relaxed), 0);
+        drop(rx);
+        assert_eq!(drops.load(Relaxed), 1);
+    }
+
+    #[test]
+    fn closed_wakes_sender() {
+        let (tx, rx) = channel::<i32>();
+        thread::scope(|s| {
+            s.spawn(move || {
+                assert!(!tx.is_closed());
+                tx.closed();
+                assert_eq!(tx.send(1), Err(1));
+            });
+            thread::sleep(Duration::from_millis(20));
+            drop(rx);
+        });
+    }
+
+    #[test]
+    fn racing_drops() {
+        let drops = &AtomicUsize::new(0);
+        thread::scope(|s| {
+            for i in 0..ROUNDS {
+                let (tx, rx) = channel();
+                s.spawn(move || tx.send(Tracked(drops)));
+                if i % 2 == 0 {
+                    drop(rx.recv());
+                } else {
+                    drop(rx);
+                }
+                let (tx, rx) = channel::<Tracked>();
+                s.spawn(move || drop(tx));
+                drop(rx);
+            }
+        });
+        assert_eq!(drops.load(Relaxed), ROUNDS);
+    }
+}
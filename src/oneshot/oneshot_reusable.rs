@@ -0,0 +1,225 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::Ordering::*;
+use std::sync::Arc;
+use std::{mem::MaybeUninit, sync::atomic::AtomicUsize};
+
+use super::RecvError;
+use crate::utils::wait::{wait, wake_one};
+
+/// The low bits of the state hold the slot's progress in the current round, the rest its generation.
+const FLAGS: usize = 0b11;
+const GENERATION: usize = FLAGS + 1;
+
+const EMPTY: usize = 0;
+/// The sender of the current round is writing the message.
+const WRITING: usize = 1;
+const FULL: usize = 2;
+/// The sender was dropped without sending, or the message has already been received.
+const DISCONNECTED: usize = 3;
+
+/// A oneshot slot that is allocated once and reused for one message per round.
+pub struct ReusableOneShot<T> {
+    message: UnsafeCell<MaybeUninit<T>>,
+    /// Futex word the receiver waits on.
+    state: AtomicUsize,
+}
+
+unsafe impl<T> Sync for ReusableOneShot<T> where T: Send {}
+
+impl<T> ReusableOneShot<T> {
+    pub const fn new() -> Self {
+        Self {
+            message: UnsafeCell::new(MaybeUninit::uninit()),
+            state: AtomicUsize::new(EMPTY),
+        }
+    }
+
+    /// Moves the slot on to the next generation, dropping a message left unreceived,
+    /// so that only senders minted afterwards can write into it. Returns the new generation.
+    fn advance(&self) -> usize {
+        let mut state = self.state.load(Acquire);
+        loop {
+            if state & FLAGS == WRITING {
+                wait(&self.state, state);
+                state = self.state.load(Acquire);
+                continue;
+            }
+            let next = (state & !FLAGS).wrapping_add(GENERATION);
+            match self.state.compare_exchange(state, next, AcqRel, Acquire) {
+                Ok(_) => {
+                    if state & FLAGS == FULL {
+                        unsafe { (*self.message.get()).assume_init_drop() }
+                    }
+                    return next;
+                }
+                Err(s) => state = s,
+            }
+        }
+    }
+}
+
+impl<T> Default for ReusableOneShot<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for ReusableOneShot<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() & FLAGS == FULL {
+            unsafe { self.message.get_mut().assume_init_drop() }
+        }
+    }
+}
+
+/// Sends the message of one round. Fails once the receiver has moved on to another round.
+pub struct Sender<T> {
+    channel: Arc<ReusableOneShot<T>>,
+    generation: usize,
+}
+
+pub struct Receiver<T> {
+    channel: Arc<ReusableOneShot<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends `message`, or hands it back if this sender belongs to a past round
+    /// or the receiver has been dropped.
+    pub fn send(self, message: T) -> Result<(), T> {
+        if self
+            .channel
+            .state
+            .compare_exchange(self.generation, self.generation | WRITING, Acquire, Relaxed)
+            .is_err()
+        {
+            return Err(message);
+        }
+        unsafe { (*self.channel.message.get()).write(message) };
+        self.channel.state.store(self.generation | FULL, Release);
+        wake_one(&self.channel.state);
+        Ok(())
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if self
+            .channel
+            .state
+            .compare_exchange(
+                self.generation,
+                self.generation | DISCONNECTED,
+                Release,
+                Relaxed,
+            )
+            .is_ok()
+        {
+            wake_one(&self.channel.state);
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Returns `true` if `recv` would not block.
+    pub fn is_ready(&self) -> bool {
+        matches!(
+            self.channel.state.load(Relaxed) & FLAGS,
+            FULL | DISCONNECTED
+        )
+    }
+
+    /// Blocks until this round's message arrives, or fails if its sender is dropped without sending.
+    /// Once it has returned, call `sender` to start the next round.
+    pub fn recv(&mut self) -> Result<T, RecvError> {
+        loop {
+            let state = self.channel.state.load(Acquire);
+            match state & FLAGS {
+                FULL => {
+                    let message = unsafe { (*self.channel.message.get()).assume_init_read() };
+                    self.channel
+                        .state
+                        .store(state & !FLAGS | DISCONNECTED, Relaxed);
+                    return Ok(message);
+                }
+                DISCONNECTED => return Err(RecvError),
+                _ => wait(&self.channel.state, state),
+            }
+        }
+    }
+
+    /// Starts a new round and returns its sender. The sender of the previous round
+    /// can no longer send, and a message it left unreceived is dropped.
+    pub fn sender(&mut self) -> Sender<T> {
+        Sender {
+            channel: self.channel.clone(),
+            generation: self.channel.advance(),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.channel.advance();
+    }
+}
+
+/// Creates a reusable oneshot channel, returning the sender of its first round.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let channel = Arc::new(ReusableOneShot::<T>::new());
+
+    (
+        Sender {
+            channel: channel.clone(),
+            generation: 0,
+        },
+        Receiver { channel },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn request_loop() {
+        let (mut tx, mut rx) = channel();
+        thread::scope(|s| {
+            for i in 0..100 {
+                s.spawn(move || tx.send(i).unwrap());
+                assert_eq!(rx.recv(), Ok(i));
+                tx = rx.sender();
+            }
+        });
+    }
+
+    #[test]
+    fn stale_sender_fails() {
+        let (stale, mut rx) = channel();
+        let tx = rx.sender();
+        assert_eq!(stale.send(1), Err(1));
+        assert!(!rx.is_ready());
+        tx.send(2).unwrap();
+        assert_eq!(rx.recv(), Ok(2));
+
+        let tx = rx.sender();
+        drop(tx);
+        assert_eq!(rx.recv(), Err(RecvError));
+
+        let tx = rx.sender();
+        drop(rx);
+        assert_eq!(tx.send(3), Err(3));
+    }
+
+    #[test]
+    fn unreceived_message_dropped() {
+        let message = Arc::new(());
+        let (tx, mut rx) = channel();
+        tx.send(message.clone()).unwrap();
+        let tx = rx.sender();
+        assert_eq!(Arc::strong_count(&message), 1);
+        tx.send(message.clone()).unwrap();
+        drop(rx);
+        assert_eq!(Arc::strong_count(&message), 1);
+    }
+}
@@ -0,0 +1,133 @@
+use std::time::Duration;
+
+use super::{
+    recv_timeout_result, try_recv_result, RecvError, RecvTimeoutError, Slot, TryRecvError,
+};
+
+/// A oneshot slot that lives wherever its owner puts it, typically on the stack.
+/// Its handles borrow it, so handing a message off allocates nothing.
+pub struct OneShot<T> {
+    slot: Slot<T>,
+}
+
+impl<T> OneShot<T> {
+    pub const fn new() -> Self {
+        Self { slot: Slot::new() }
+    }
+
+    /// Splits the slot into handles borrowing it for `'a`, for use with [`std::thread::scope`].
+    /// A slot can be split again once both handles are gone; a message left unreceived
+    /// by the previous pair is dropped.
+    pub fn split<'a>(&'a mut self) -> (Sender<'a, T>, Receiver<'a, T>) {
+        self.slot.reset();
+        let channel = &self.slot;
+        (Sender { channel }, Receiver { channel })
+    }
+}
+
+impl<T> Default for OneShot<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Sender<'a, T> {
+    channel: &'a Slot<T>,
+}
+
+pub struct Receiver<'a, T> {
+    channel: &'a Slot<T>,
+}
+
+impl<T> Sender<'_, T> {
+    /// Sends `message`, or hands it back if the receiver has been dropped.
+    pub fn send(self, message: T) -> Result<(), T> {
+        self.channel.send(message)
+    }
+
+    /// Returns `true` if the receiver has been dropped, so sending would fail.
+    pub fn is_closed(&self) -> bool {
+        self.channel.is_closed()
+    }
+
+    /// Blocks until the receiver has been dropped.
+    pub fn closed(&self) {
+        self.channel.closed()
+    }
+}
+
+impl<T> Drop for Sender<'_, T> {
+    fn drop(&mut self) {
+        self.channel.disconnect();
+    }
+}
+
+impl<T> Receiver<'_, T> {
+    /// Returns `true` if `recv` would not block.
+    pub fn is_ready(&self) -> bool {
+        self.channel.is_ready()
+    }
+
+    /// Blocks until the message arrives, or fails if the sender is dropped without sending.
+    pub fn recv(self) -> Result<T, RecvError> {
+        self.channel.recv()
+    }
+
+    /// Takes the message without blocking.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        try_recv_result(self.channel.take())
+    }
+
+    /// Like `recv`, but gives up once `timeout` has passed and hands the receiver back.
+    pub fn recv_timeout(self, timeout: Duration) -> Result<T, RecvTimeoutError<Self>> {
+        recv_timeout_result(self.channel.recv_timeout(timeout), self)
+    }
+}
+
+impl<T> Drop for Receiver<'_, T> {
+    fn drop(&mut self) {
+        self.channel.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn send_recv_in_scope() {
+        let mut oneshot = OneShot::new();
+        thread::scope(|s| {
+            let (tx, rx) = oneshot.split();
+            s.spawn(move || tx.send(vec![1, 2, 3]));
+            assert_eq!(rx.recv(), Ok(vec![1, 2, 3]));
+        });
+    }
+
+    #[test]
+    fn dropped_sender_disconnects() {
+        let mut oneshot = OneShot::<i32>::new();
+        let (tx, rx) = oneshot.split();
+        thread::scope(|s| {
+            s.spawn(move || drop(tx));
+        });
+        assert_eq!(rx.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn split_again() {
+        let mut oneshot = OneShot::new();
+        let (tx, rx) = oneshot.split();
+        tx.send(String::from("unreceived")).unwrap();
+        drop(rx);
+        for i in 0..3 {
+            let (tx, rx) = oneshot.split();
+            assert!(!rx.is_ready());
+            thread::scope(|s| {
+                s.spawn(move || tx.send(i.to_string()));
+            });
+            assert_eq!(rx.recv(), Ok(i.to_string()));
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::Ordering::*;
+use std::sync::Arc;
+use std::{mem::MaybeUninit, sync::atomic::AtomicUsize};
+
+use super::RecvError;
+use crate::utils::wait::{wait, wake_all};
+
+const EMPTY: usize = 0;
+const FULL: usize = 1;
+/// The sender was dropped without sending.
+const DISCONNECTED: usize = 2;
+
+/// A oneshot slot whose message is kept once sent, for any number of receivers to read.
+pub struct OneShot<T> {
+    message: UnsafeCell<MaybeUninit<T>>,
+    /// Futex word every receiver waits on.
+    state: AtomicUsize,
+    receivers: AtomicUsize,
+}
+
+// Safety: the message is only written before `FULL` is published, then shared by reference.
+unsafe impl<T> Sync for OneShot<T> where T: Send + Sync {}
+
+impl<T> OneShot<T> {
+    pub const fn new() -> Self {
+        Self {
+            message: UnsafeCell::new(MaybeUninit::uninit()),
+            state: AtomicUsize::new(EMPTY),
+            receivers: AtomicUsize::new(1),
+        }
+    }
+}
+
+impl<T> Default for OneShot<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for OneShot<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == FULL {
+            unsafe { self.message.get_mut().assume_init_drop() }
+        }
+    }
+}
+
+pub struct Sender<T> {
+    channel: Arc<OneShot<T>>,
+}
+
+/// Every clone receives the same message.
+pub struct Receiver<T> {
+    channel: Arc<OneShot<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Publishes `message` and wakes every waiting receiver at once,
+    /// or hands it back if every receiver has been dropped.
+    pub fn send(self, message: T) -> Result<(), T> {
+        if self.is_closed() {
+            return Err(message);
+        }
+        unsafe { (*self.channel.message.get()).write(message) };
+        self.channel.state.store(FULL, Release);
+        wake_all(&self.channel.state);
+        Ok(())
+    }
+
+    /// Returns `true` if every receiver has been dropped, so sending would fail.
+    pub fn is_closed(&self) -> bool {
+        self.channel.receivers.load(Acquire) == 0
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if self
+            .channel
+            .state
+            .compare_exchange(EMPTY, DISCONNECTED, Release, Relaxed)
+            .is_ok()
+        {
+            wake_all(&self.channel.state);
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Returns `true` if `recv` would not block.
+    pub fn is_ready(&self) -> bool {
+        self.channel.state.load(Relaxed) != EMPTY
+    }
+
+    /// Blocks until the message arrives and borrows it, or fails if the sender is dropped without sending.
+    pub fn recv_ref(&self) -> Result<&T, RecvError> {
+        loop {
+            match self.channel.state.load(Acquire) {
+                FULL => return Ok(unsafe { (*self.channel.message.get()).assume_init_ref() }),
+                DISCONNECTED => return Err(RecvError),
+                _ => wait(&self.channel.state, EMPTY),
+            }
+        }
+    }
+
+    /// Blocks until the message arrives and returns a copy of it.
+    pub fn recv(&self) -> Result<T, RecvError>
+    where
+        T: Clone,
+    {
+        self.recv_ref().cloned()
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.channel.receivers.fetch_add(1, Relaxed);
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.channel.receivers.fetch_sub(1, Release);
+    }
+}
+
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let channel = Arc::new(OneShot::<T>::new());
+
+    (
+        Sender {
+            channel: channel.clone(),
+        },
+        Receiver { channel },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn every_receiver_gets_the_message() {
+        let (tx, rx) = channel();
+        thread::scope(|s| {
+            let waiters: Vec<_> = (0..8)
+                .map(|_| {
+                    let rx = rx.clone();
+                    s.spawn(move || rx.recv())
+                })
+                .collect();
+            thread::sleep(std::time::Duration::from_millis(20));
+            tx.send(String::from("ready")).unwrap();
+            for waiter in waiters {
+                assert_eq!(waiter.join().unwrap().as_deref(), Ok("ready"));
+            }
+        });
+        assert_eq!(rx.recv_ref().map(String::as_str), Ok("ready"));
+    }
+
+    #[test]
+    fn send_fails_once_every_receiver_is_gone() {
+        let (tx, rx) = channel();
+        let rx1 = rx.clone();
+        drop(rx);
+        assert!(!tx.is_closed());
+        drop(rx1);
+        assert!(tx.is_closed());
+        assert_eq!(tx.send(1), Err(1));
+    }
+
+    #[test]
+    fn dropped_sender_disconnects_every_receiver() {
+        let (tx, rx) = channel::<i32>();
+        thread::scope(|s| {
+            let waiters: Vec<_> = (0..4)
+                .map(|_| {
+                    let rx = rx.clone();
+                    s.spawn(move || rx.recv())
+                })
+                .collect();
+            drop(tx);
+            for waiter in waiters {
+                assert_eq!(waiter.join().unwrap(), Err(RecvError));
+            }
+        });
+    }
+}
@@ -0,0 +1,100 @@
+use std::hash::{BuildHasher, Hash};
+
+use crate::mpsc::{self, Receiver, SendError};
+
+/// Routes every message to one of `n` partitions by the hash of its key,
+/// so that messages with the same key always reach the same [`Receiver`].
+pub struct Sender<T, S> {
+    partitions: Vec<mpsc::Sender<T>>,
+    hasher: S,
+}
+
+impl<T, S: BuildHasher> Sender<T, S> {
+    /// Sends `data` to the partition owning `key`.
+    /// Fails if that partition's receiver has been dropped; other partitions are unaffected.
+    #[inline]
+    pub fn send<K: Hash + ?Sized>(&self, key: &K, data: T) -> Result<(), SendError<T>> {
+        self.partitions[self.partition(key)].send(data)
+    }
+
+    /// Returns the index of the partition owning `key`.
+    #[inline]
+    pub fn partition<K: Hash + ?Sized>(&self, key: &K) -> usize {
+        (self.hasher.hash_one(key) % self.partitions.len() as u64) as usize
+    }
+
+    /// Returns the number of partitions.
+    #[inline]
+    pub fn partitions(&self) -> usize {
+        self.partitions.len()
+    }
+}
+
+impl<T, S: Clone> Clone for Sender<T, S> {
+    fn clone(&self) -> Self {
+        Self {
+            partitions: self.partitions.clone(),
+            hasher: self.hasher.clone(),
+        }
+    }
+}
+
+/// Creates a channel with `n` partitions, each with its own [`Receiver`].
+/// A partition disconnects once every [`Sender`] is gone.
+///
+/// # Panics
+///
+/// Panics if `n` is zero.
+pub fn channel<T, S: BuildHasher>(n: usize, hasher: S) -> (Sender<T, S>, Vec<Receiver<T>>) {
+    assert!(n > 0, "partitioned channel needs at least one partition");
+    let (partitions, receivers) = (0..n).map(|_| mpsc::channel()).unzip();
+
+    (Sender { partitions, hasher }, receivers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mpsc::RecvError;
+    use std::collections::hash_map::RandomState;
+    use std::thread;
+
+    #[test]
+    fn same_key_same_partition() {
+        let (tx, rxs) = channel(4, RandomState::new());
+        for i in 0..100 {
+            tx.send(&(i % 10), i).unwrap();
+        }
+        drop(tx);
+        thread::scope(|s| {
+            let handles: Vec<_> = rxs
+                .iter()
+                .map(|rx| s.spawn(move || rx.into_iter().map(|i| i % 10).collect::<Vec<_>>()))
+                .collect();
+            let mut seen = Vec::new();
+            for handle in handles {
+                let mut keys = handle.join().unwrap();
+                keys.sort();
+                keys.dedup();
+                for key in keys {
+                    assert!(!seen.contains(&key));
+                    seen.push(key);
+                }
+            }
+            assert_eq!(seen.len(), 10);
+        });
+    }
+
+    #[test]
+    fn partitions_disconnect_independently() {
+        let (tx, mut rxs) = channel(2, RandomState::new());
+        let key = (0..).find(|k| tx.partition(k) == 0).unwrap();
+        let other = (0..).find(|k| tx.partition(k) == 1).unwrap();
+        drop(rxs.remove(0));
+        assert!(tx.send(&key, "lost").is_err());
+        tx.send(&other, "kept").unwrap();
+        drop(tx);
+        assert_eq!(rxs[0].recv(), Ok("kept"));
+        assert_eq!(rxs[0].recv(), Err(RecvError::Disconnected));
+    }
+}
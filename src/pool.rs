@@ -0,0 +1,173 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use std::thread::{self, JoinHandle};
+
+use crate::oneshot::oneshot_arc;
+use crate::utils::{
+    queue::Queue,
+    wait::{wait, wake_all, wake_one},
+};
+use crossbeam_epoch::pin;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Set in `Shared::jobs` once the pool stops accepting work.
+const SHUTDOWN: usize = 1;
+/// One queued job, counted above the `SHUTDOWN` bit.
+const JOB: usize = 2;
+
+struct Shared {
+    queue: Queue<Job>,
+    jobs: AtomicUsize,
+}
+
+impl Shared {
+    fn push(&self, job: Job) {
+        let guard = &pin();
+        self.queue.push(job, guard);
+        self.jobs.fetch_add(JOB, Ordering::Release);
+        wake_one(&self.jobs);
+    }
+
+    /// Runs jobs until the pool is shut down and the queue is drained.
+    fn work(&self) {
+        loop {
+            let jobs = self.jobs.load(Ordering::Acquire);
+            if jobs >= JOB {
+                if self
+                    .jobs
+                    .compare_exchange(jobs, jobs - JOB, Ordering::Acquire, Ordering::Relaxed)
+                    .is_err()
+                {
+                    continue;
+                }
+                let job = self.queue.try_pop(&pin());
+                if let Some(job) = job {
+                    job();
+                }
+            } else if jobs & SHUTDOWN != 0 {
+                return;
+            } else {
+                wait(&self.jobs, jobs);
+            }
+        }
+    }
+}
+
+pub struct ThreadPool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ThreadPool {
+    /// Spawns a pool of `n` worker threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn new(n: usize) -> Self {
+        assert!(n > 0, "thread pool needs at least one worker");
+        let shared = Arc::new(Shared {
+            queue: Queue::new(),
+            jobs: AtomicUsize::new(0),
+        });
+        let workers = (0..n)
+            .map(|_| {
+                let shared = shared.clone();
+                thread::spawn(move || shared.work())
+            })
+            .collect();
+
+        Self { shared, workers }
+    }
+
+    /// Runs `f` on one of the workers. A panic inside `f` is caught, so the worker keeps running.
+    pub fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.shared.push(Box::new(move || {
+            let _ = catch_unwind(AssertUnwindSafe(f));
+        }));
+    }
+
+    /// Runs `f` on one of the workers and returns a receiver for its result.
+    /// If `f` panics, the panic payload is delivered as the `Err` variant.
+    pub fn spawn<F, R>(&self, f: F) -> oneshot_arc::Receiver<thread::Result<R>>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = oneshot_arc::channel();
+        self.shared.push(Box::new(move || {
+            // Nobody is left to tell if the caller dropped the receiver.
+            let _ = tx.send(catch_unwind(AssertUnwindSafe(f)));
+        }));
+        rx
+    }
+
+    /// Returns the number of worker threads.
+    #[inline]
+    pub fn workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Waits for every queued job to finish, then stops the workers.
+    pub fn join(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.shared.jobs.fetch_or(SHUTDOWN, Ordering::Release);
+        wake_all(&self.shared.jobs);
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+
+    #[test]
+    fn join_runs_every_job() {
+        let pool = ThreadPool::new(4);
+        let counter = Arc::new(AtomicUsize::new(0));
+        for _ in 0..1000 {
+            let counter = counter.clone();
+            pool.execute(move || {
+                counter.fetch_add(1, SeqCst);
+            });
+        }
+        pool.join();
+        assert_eq!(counter.load(SeqCst), 1000);
+    }
+
+    #[test]
+    fn spawn_returns_result() {
+        let pool = ThreadPool::new(2);
+        let rx = pool.spawn(|| 40 + 2);
+        assert_eq!(rx.recv().unwrap().unwrap(), 42);
+    }
+
+    #[test]
+    fn panic_is_reported_and_worker_survives() {
+        let pool = ThreadPool::new(1);
+        let rx = pool.spawn(|| panic!("boom"));
+        assert!(rx.recv().unwrap().is_err());
+        pool.execute(|| panic!("boom"));
+        let rx = pool.spawn(|| 1);
+        assert_eq!(rx.recv().unwrap().unwrap(), 1);
+    }
+}
@@ -0,0 +1,204 @@
+use std::sync::{
+    atomic::{self, AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
+use crate::mpsc::{RecvError, SendError};
+use crate::utils::{claims::Claims, queue::Queue};
+use crossbeam_epoch::pin;
+
+pub struct Channel<T> {
+    queue: Queue<T>,
+    capacity: usize,
+    /// Number of messages the receiver may claim, plus one once the last sender is gone.
+    messages: Claims,
+    senders: AtomicUsize,
+    /// Messages evicted since the receiver last asked.
+    overwritten: AtomicUsize,
+    closed: AtomicBool,
+}
+
+impl<T> Channel<T> {
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "ring channel needs room for one message");
+        Self {
+            queue: Queue::new(),
+            capacity,
+            messages: Claims::new(),
+            senders: AtomicUsize::new(1),
+            overwritten: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+        }
+    }
+}
+
+pub struct Sender<T> {
+    channel: Arc<Channel<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends `data` without ever blocking. If the ring is full, the oldest unread
+    /// message is evicted and returned. Fails if the receiver has been dropped.
+    #[inline]
+    pub fn send(&self, data: T) -> Result<Option<T>, SendError<T>> {
+        if self.channel.closed.load(Ordering::Acquire) {
+            return Err(SendError(data));
+        }
+        let guard = &pin();
+        self.channel.queue.push(data, guard);
+        let messages = self.channel.messages.notify();
+
+        let mut evicted = None;
+        // Evicting goes through the same claim as receiving, so the two never pop the same slot twice.
+        if messages >= self.channel.capacity && self.channel.messages.try_claim() {
+            evicted = self.channel.queue.try_pop(guard);
+            if evicted.is_some() {
+                self.channel.overwritten.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        if self.channel.closed.load(Ordering::SeqCst) {
+            while self.channel.queue.try_pop(guard).is_some() {}
+        }
+        Ok(evicted)
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.channel.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if self.channel.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.channel.messages.notify();
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    channel: Arc<Channel<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Returns `true` if a message is waiting. A pending disconnect does not count.
+    #[inline]
+    pub fn ready(&self) -> bool {
+        !self.channel.queue.is_empty(&pin())
+    }
+
+    /// Returns how many messages were overwritten before being received, since the last call.
+    #[inline]
+    pub fn take_overwritten(&self) -> usize {
+        self.channel.overwritten.swap(0, Ordering::Relaxed)
+    }
+
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let guard = &pin();
+        if self.channel.messages.count() < 1 && self.channel.senders.load(Ordering::Acquire) < 1 {
+            return Err(RecvError::Disconnected);
+        }
+        self.channel.messages.claim();
+
+        self.channel
+            .queue
+            .try_pop(guard)
+            .ok_or(RecvError::Disconnected)
+    }
+
+    pub fn try_recv(&self) -> Option<T> {
+        if !self.channel.messages.try_claim() {
+            return None;
+        }
+        let guard = &pin();
+        let data = self.channel.queue.try_pop(guard);
+        if data.is_none() {
+            self.channel.messages.unclaim();
+        }
+        data
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.channel.closed.store(true, Ordering::SeqCst);
+        atomic::fence(Ordering::SeqCst);
+        let guard = &pin();
+        while self.channel.queue.try_pop(guard).is_some() {}
+    }
+}
+
+/// Creates a channel holding the latest `capacity` messages. Sending never blocks:
+/// once the ring is full, every new message overwrites the oldest unread one.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let channel = Arc::new(Channel::new(capacity));
+
+    (
+        Sender {
+            channel: channel.clone(),
+        },
+        Receiver { channel },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn overwrites_oldest() {
+        let (tx, rx) = channel(3);
+        for i in 0..3 {
+            assert_eq!(tx.send(i), Ok(None));
+        }
+        assert_eq!(tx.send(3), Ok(Some(0)));
+        assert_eq!(tx.send(4), Ok(Some(1)));
+        assert_eq!(rx.take_overwritten(), 2);
+        assert_eq!(rx.take_overwritten(), 0);
+        drop(tx);
+        let received: Vec<_> = std::iter::from_fn(|| rx.recv().ok()).collect();
+        assert_eq!(received, [2, 3, 4]);
+        assert!(!rx.ready());
+        assert_eq!(rx.recv(), Err(RecvError::Disconnected));
+    }
+
+    #[test]
+    fn receives_latest_under_load() {
+        let (tx, rx) = channel(4);
+        thread::scope(|s| {
+            s.spawn(move || {
+                for i in 0..100_000 {
+                    tx.send(i).unwrap();
+                }
+            });
+            let mut last = -1;
+            let mut received = 0;
+            while let Ok(i) = rx.recv() {
+                assert!(i > last);
+                last = i;
+                received += 1;
+            }
+            assert_eq!(last, 99_999);
+            assert_eq!(received + rx.take_overwritten(), 100_000);
+        });
+    }
+
+    #[test]
+    fn send_fails_after_receiver_drop() {
+        let (tx, rx) = channel(1);
+        drop(rx);
+        assert_eq!(tx.send(1), Err(SendError(1)));
+    }
+}
@@ -0,0 +1,372 @@
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, Mutex, MutexGuard,
+};
+
+use crate::utils::{claims::Claims, queue::Queue};
+use crossbeam_epoch::{pin, Guard};
+
+/// Serialization used to page messages out to disk.
+pub trait Codec: Sized {
+    fn encode(&self, buf: &mut Vec<u8>);
+    fn decode(buf: &[u8]) -> io::Result<Self>;
+}
+
+impl Codec for Vec<u8> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self);
+    }
+
+    fn decode(buf: &[u8]) -> io::Result<Self> {
+        Ok(buf.to_vec())
+    }
+}
+
+impl Codec for String {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(buf: &[u8]) -> io::Result<Self> {
+        String::from_utf8(buf.to_vec()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Length-prefixed records appended to a file. Once every record has been read back
+/// the file is truncated, so it only grows while the receiver lags behind.
+struct Disk {
+    file: File,
+    path: PathBuf,
+    read: u64,
+    write: u64,
+    records: usize,
+}
+
+impl Disk {
+    fn append(&mut self, record: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(record.len())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        self.file.write_all_at(&len.to_le_bytes(), self.write)?;
+        self.file.write_all_at(record, self.write + 4)?;
+        self.write += 4 + record.len() as u64;
+        self.records += 1;
+        Ok(())
+    }
+
+    fn next(&mut self) -> io::Result<Vec<u8>> {
+        let mut len = [0; 4];
+        self.file.read_exact_at(&mut len, self.read)?;
+        let mut record = vec![0; u32::from_le_bytes(len) as usize];
+        self.file.read_exact_at(&mut record, self.read + 4)?;
+        self.read += 4 + record.len() as u64;
+        self.records -= 1;
+        if self.records == 0 {
+            self.file.set_len(0)?;
+            self.read = 0;
+            self.write = 0;
+        }
+        Ok(record)
+    }
+}
+
+impl Drop for Disk {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+pub struct Channel<T> {
+    queue: Queue<T>,
+    /// Messages available to the receiver, in memory or on disk. The last sender
+    /// to go away adds one extra claim with no message behind it, so that a
+    /// blocked receiver wakes up and observes the disconnect.
+    messages: Claims,
+    senders: AtomicUsize,
+    /// Messages held by `queue`.
+    in_memory: AtomicUsize,
+    threshold: usize,
+    /// Set while `disk` holds messages. New messages go to disk as well,
+    /// so that they are read back after the ones already paged out.
+    spilling: AtomicBool,
+    disk: Mutex<Disk>,
+}
+
+impl<T: Codec> Channel<T> {
+    /// Creates a channel keeping at most `threshold` messages in memory, the rest is written to `path`.
+    /// The file is truncated on creation and removed when the channel is dropped.
+    pub fn new(threshold: usize, path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        Ok(Self {
+            queue: Queue::new(),
+            messages: Claims::new(),
+            senders: AtomicUsize::new(1),
+            in_memory: AtomicUsize::new(0),
+            threshold,
+            spilling: AtomicBool::new(false),
+            disk: Mutex::new(Disk {
+                file,
+                path,
+                read: 0,
+                write: 0,
+                records: 0,
+            }),
+        })
+    }
+
+    fn disk(&self) -> MutexGuard<'_, Disk> {
+        self.disk.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn push(&self, data: T, guard: &Guard) -> io::Result<()> {
+        if !self.spilling.load(Ordering::Acquire) && self.reserve_in_memory() {
+            self.queue.push(data, guard);
+            return Ok(());
+        }
+        let mut buf = Vec::new();
+        data.encode(&mut buf);
+        let mut disk = self.disk();
+        disk.append(&buf)?;
+        self.spilling.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Takes one of the `threshold` in-memory slots, if any is left.
+    #[inline]
+    fn reserve_in_memory(&self) -> bool {
+        self.in_memory
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                (n < self.threshold).then_some(n + 1)
+            })
+            .is_ok()
+    }
+
+    /// Pops the oldest message: memory is drained first, since everything on disk was sent after it.
+    fn pop(&self, guard: &Guard) -> io::Result<Option<T>> {
+        if let Some(data) = self.queue.try_pop(guard) {
+            self.in_memory.fetch_sub(1, Ordering::Relaxed);
+            return Ok(Some(data));
+        }
+        let mut disk = self.disk();
+        if disk.records == 0 {
+            return Ok(None);
+        }
+        let record = disk.next()?;
+        if disk.records == 0 {
+            self.spilling.store(false, Ordering::Release);
+        }
+        T::decode(&record).map(Some)
+    }
+}
+
+pub struct Sender<T> {
+    channel: Arc<Channel<T>>,
+}
+
+impl<T: Codec> Sender<T> {
+    /// Sends `data` without blocking. Fails only if paging the message out to disk fails.
+    #[inline]
+    pub fn send(&self, data: T) -> io::Result<()> {
+        let guard = &pin();
+        self.channel.push(data, guard)?;
+        self.channel.messages.notify();
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.channel.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if self.channel.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.channel.messages.notify();
+        }
+    }
+}
+
+pub struct Receiver<T> {
+    channel: Arc<Channel<T>>,
+}
+
+#[derive(Debug)]
+pub enum RecvError {
+    Disconnected,
+    Io(io::Error),
+}
+
+impl From<io::Error> for RecvError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl<T: Codec> Receiver<T> {
+    /// Returns the senders remaining of this [`Channel<T>`].
+    #[inline]
+    fn senders_remaining(&self) -> usize {
+        self.channel.senders.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    fn messages_remaining(&self) -> usize {
+        self.channel.messages.count()
+    }
+
+    /// Returns `true` if a message is waiting, in memory or on disk.
+    /// A pending disconnect does not count.
+    #[inline]
+    pub fn ready(&self) -> bool {
+        self.in_memory() > 0 || self.channel.spilling.load(Ordering::Acquire)
+    }
+
+    /// Returns the number of messages currently held in memory.
+    #[inline]
+    pub fn in_memory(&self) -> usize {
+        self.channel.in_memory.load(Ordering::Relaxed)
+    }
+
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let guard = &pin();
+        if self.messages_remaining() < 1 && self.senders_remaining() < 1 {
+            return Err(RecvError::Disconnected);
+        }
+        self.channel.messages.claim();
+
+        self.channel.pop(guard)?.ok_or(RecvError::Disconnected)
+    }
+
+    pub fn try_recv(&self) -> Result<Option<T>, RecvError> {
+        if !self.channel.messages.try_claim() {
+            return Ok(None);
+        }
+        let guard = &pin();
+        let data = self.channel.pop(guard)?;
+        if data.is_none() {
+            self.channel.messages.unclaim();
+        }
+        Ok(data)
+    }
+}
+
+/// Creates an unbounded channel that keeps up to `threshold` messages in memory
+/// and pages the overflow out to the file at `path`, preserving FIFO order.
+pub fn channel<T: Codec>(
+    threshold: usize,
+    path: impl AsRef<Path>,
+) -> io::Result<(Sender<T>, Receiver<T>)> {
+    let channel = Arc::new(Channel::new(threshold, path)?);
+
+    Ok((
+        Sender {
+            channel: channel.clone(),
+        },
+        Receiver { channel },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("channels-spill-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn spills_and_keeps_order() {
+        let path = path("order");
+        let (tx, rx) = channel::<String>(4, &path).unwrap();
+        for i in 0..100 {
+            tx.send(i.to_string()).unwrap();
+        }
+        assert_eq!(rx.in_memory(), 4);
+        assert!(path.metadata().unwrap().len() > 0);
+        for i in 0..50 {
+            assert_eq!(rx.recv().unwrap(), i.to_string());
+        }
+        tx.send("tail".to_string()).unwrap();
+        for i in 50..100 {
+            assert_eq!(rx.recv().unwrap(), i.to_string());
+        }
+        assert_eq!(rx.recv().unwrap(), "tail");
+        assert_eq!(path.metadata().unwrap().len(), 0);
+
+        tx.send("memory".to_string()).unwrap();
+        assert_eq!(rx.in_memory(), 1);
+        drop(tx);
+        assert_eq!(rx.recv().unwrap(), "memory");
+        assert!(matches!(rx.recv(), Err(RecvError::Disconnected)));
+        drop(rx);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn blocked_recv_wakes_on_disconnect() {
+        let (tx, rx) = channel::<String>(4, path("disconnect")).unwrap();
+        thread::scope(|s| {
+            s.spawn(|| assert!(matches!(rx.recv(), Err(RecvError::Disconnected))));
+            thread::sleep(std::time::Duration::from_millis(50));
+            drop(tx);
+        });
+        assert!(!rx.ready());
+        assert!(matches!(rx.try_recv(), Ok(None)));
+    }
+
+    #[test]
+    fn concurrent_senders_respect_threshold() {
+        let (tx, rx) = channel::<String>(4, path("threshold")).unwrap();
+        thread::scope(|s| {
+            for _ in 0..8 {
+                let tx = tx.clone();
+                s.spawn(move || {
+                    for i in 0..200 {
+                        tx.send(i.to_string()).unwrap();
+                    }
+                });
+            }
+        });
+        assert!(rx.in_memory() <= 4);
+        assert_eq!((0..1600).filter(|_| rx.recv().is_ok()).count(), 1600);
+    }
+
+    #[test]
+    fn per_sender_order_across_threads() {
+        let (tx, rx) = channel::<String>(8, path("threads")).unwrap();
+        thread::scope(|s| {
+            for t in 0..4 {
+                let tx = tx.clone();
+                s.spawn(move || {
+                    for i in 0..1000 {
+                        tx.send(format!("{t}:{i}")).unwrap();
+                    }
+                });
+            }
+            drop(tx);
+            let mut next = [0; 4];
+            for _ in 0..4000 {
+                let msg = rx.recv().unwrap();
+                let (t, i) = msg.split_once(':').unwrap();
+                let t: usize = t.parse().unwrap();
+                assert_eq!(i.parse::<usize>().unwrap(), next[t]);
+                next[t] += 1;
+            }
+        });
+    }
+}
@@ -0,0 +1,71 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::wait::{wait, wake_one};
+
+/// Number of messages a receiver may claim, and the futex word it blocks on.
+///
+/// Events that must wake a blocked receiver without a message, such as the last sender
+/// going away, add a claim with no message behind it. Whoever takes such a claim finds
+/// the queue empty and reports the event instead.
+pub struct Claims(AtomicUsize);
+
+impl Claims {
+    pub const fn new() -> Self {
+        Self(AtomicUsize::new(0))
+    }
+
+    /// Returns the number of claims, including those standing for an event.
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// The futex word `add` changes.
+    #[inline]
+    pub fn futex(&self) -> &AtomicUsize {
+        &self.0
+    }
+
+    /// Adds one claim without waking anyone, returning the previous count.
+    #[inline]
+    pub fn add(&self) -> usize {
+        self.0.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// Adds one claim and wakes a blocked receiver, returning the previous count.
+    #[inline]
+    pub fn notify(&self) -> usize {
+        let previous = self.add();
+        wake_one(&self.0);
+        previous
+    }
+
+    /// Takes one claim without blocking.
+    #[inline]
+    pub fn try_claim(&self) -> bool {
+        self.0
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1))
+            .is_ok()
+    }
+
+    /// Blocks until a claim is available and takes it.
+    #[inline]
+    pub fn claim(&self) {
+        while !self.try_claim() {
+            wait(&self.0, 0);
+        }
+    }
+
+    /// Gives back a claim that had no message behind it, leaving the event
+    /// it stands for to be reported by the next blocking receive.
+    #[inline]
+    pub fn unclaim(&self) {
+        self.0.fetch_add(1, Ordering::Release);
+    }
+}
+
+impl Default for Claims {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,2 +1,4 @@
+pub mod claims;
 pub mod queue;
+pub mod signal;
 pub mod wait;
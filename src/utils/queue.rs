@@ -160,6 +160,13 @@ impl<T> Queue<T> {
         }
     }
 
+    /// Returns `true` if the queue is observed to be empty.
+    pub(crate) fn is_empty(&self, guard: &Guard) -> bool {
+        let head = self.head.load(Acquire, guard);
+        let h = unsafe { head.deref() };
+        h.next.load(Acquire, guard).is_null()
+    }
+
     /// Attempts to dequeue from the front.
     ///
     /// Returns `None` if the queue is observed to be empty.
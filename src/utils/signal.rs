@@ -0,0 +1,66 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use super::wait::wake_one;
+use crossbeam_epoch::{pin, unprotected, Atomic, Owned, Shared};
+
+/// Futex word of a receiver that waits on several channels at once, which a channel
+/// wakes in addition to, or instead of, its own. Setting it and checking that it is unset
+/// take no lock, so channels nobody waits on this way pay next to nothing for it.
+pub struct Signal(Atomic<Arc<AtomicUsize>>);
+
+impl Signal {
+    pub const fn new() -> Self {
+        Self(Atomic::null())
+    }
+
+    /// Makes `notify` wake `signal`, or nothing once it is `None`.
+    pub fn set(&self, signal: Option<&Arc<AtomicUsize>>) {
+        let guard = &pin();
+        let new = match signal {
+            Some(signal) => Owned::new(signal.clone()).into_shared(guard),
+            None => Shared::null(),
+        };
+        let old = self.0.swap(new, Ordering::SeqCst, guard);
+        if !old.is_null() {
+            unsafe { guard.defer_destroy(old) };
+        }
+    }
+
+    /// Bumps and wakes the signal, if one is set. Returns `false` if none is.
+    #[inline]
+    pub fn notify(&self) -> bool {
+        // Telling whether the pointer is null does not dereference it, so needs no pin.
+        if unsafe { self.0.load(Ordering::SeqCst, unprotected()) }.is_null() {
+            return false;
+        }
+        let guard = &pin();
+        match unsafe { self.0.load(Ordering::SeqCst, guard).as_ref() } {
+            Some(signal) => {
+                signal.fetch_add(1, Ordering::SeqCst);
+                wake_one(signal);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Default for Signal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Signal {
+    fn drop(&mut self) {
+        unsafe {
+            let signal = self.0.load(Ordering::Relaxed, unprotected());
+            if !signal.is_null() {
+                drop(signal.into_owned());
+            }
+        }
+    }
+}
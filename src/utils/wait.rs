@@ -1,9 +1,13 @@
 use std::arch::asm;
 use std::sync::atomic::AtomicUsize;
+use std::time::Duration;
 
 #[cfg(not(target_os = "linux"))]
 compile_error!("Linux only");
 
+/// # Safety
+///
+/// `n` must be a valid syscall number and the arguments must be valid for it.
 #[inline]
 pub unsafe fn syscall4(n: u32, arg1: *const AtomicUsize, arg2: usize, arg3: usize) -> usize {
     let ret: usize;
@@ -20,6 +24,9 @@ pub unsafe fn syscall4(n: u32, arg1: *const AtomicUsize, arg2: usize, arg3: usiz
     ret
 }
 
+/// # Safety
+///
+/// `n` must be a valid syscall number and the arguments must be valid for it.
 #[inline]
 pub unsafe fn syscall5(
     n: u32,
@@ -50,9 +57,40 @@ pub fn wait(a: &AtomicUsize, expected: usize) {
     }
 }
 
+#[repr(C)]
+struct Timespec {
+    tv_sec: i64,
+    tv_nsec: i64,
+}
+
+/// Like [`wait`], but gives up once `timeout` has passed.
+#[inline]
+pub fn wait_timeout(a: &AtomicUsize, expected: usize, timeout: Duration) {
+    let timeout = Timespec {
+        tv_sec: i64::try_from(timeout.as_secs()).unwrap_or(i64::MAX),
+        tv_nsec: i64::from(timeout.subsec_nanos()),
+    };
+    unsafe {
+        syscall5(
+            202,
+            a as *const AtomicUsize,
+            0,
+            expected,
+            &timeout as *const Timespec as usize,
+        );
+    }
+}
+
 #[inline]
 pub fn wake_one(a: &AtomicUsize) {
     unsafe {
         syscall4(202, a as *const AtomicUsize, 1, 1);
     }
 }
+
+#[inline]
+pub fn wake_all(a: &AtomicUsize) {
+    unsafe {
+        syscall4(202, a as *const AtomicUsize, 1, i32::MAX as usize);
+    }
+}